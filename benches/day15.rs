@@ -0,0 +1,56 @@
+use advent_of_code_2022_rust::sensor::{
+    Point, Sensor, find_uncovered_by_scan, find_uncovered_by_scan_parallel, parse_sensors,
+};
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::{hint::black_box, thread};
+
+const EXAMPLE: &str = "Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+Sensor at x=9, y=16: closest beacon is at x=10, y=16
+Sensor at x=13, y=2: closest beacon is at x=15, y=3
+Sensor at x=12, y=14: closest beacon is at x=10, y=16
+Sensor at x=10, y=20: closest beacon is at x=10, y=16
+Sensor at x=14, y=17: closest beacon is at x=10, y=16
+Sensor at x=8, y=7: closest beacon is at x=2, y=10
+Sensor at x=2, y=0: closest beacon is at x=2, y=10
+Sensor at x=0, y=11: closest beacon is at x=2, y=10
+Sensor at x=20, y=14: closest beacon is at x=25, y=17
+Sensor at x=17, y=20: closest beacon is at x=21, y=22
+Sensor at x=16, y=7: closest beacon is at x=15, y=3
+Sensor at x=14, y=3: closest beacon is at x=15, y=3
+Sensor at x=20, y=1: closest beacon is at x=15, y=3";
+
+/// The puzzle example blown up to the real 4M x 4M search box. Its gap grows
+/// with it and starts around row 2.2M, so the scan covers about half the box.
+fn scaled_example() -> Vec<Sensor> {
+    let scale = |p: Point| Point::new(p.x * 200000, p.y * 200000);
+    parse_sensors(EXAMPLE)
+        .into_iter()
+        .map(|s| Sensor {
+            pos: scale(s.pos),
+            beacon: scale(s.beacon),
+        })
+        .collect()
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let sensors = scaled_example();
+    let (min, max) = (Point::new(0, 0), Point::new(4000000, 4000000));
+    let cores = thread::available_parallelism().map_or(4, |n| n.get());
+    let mut thread_counts = vec![2, 4, cores];
+    thread_counts.sort_unstable();
+    thread_counts.dedup();
+    let mut group = c.benchmark_group("day15_scan");
+    group.sample_size(10);
+    group.bench_function("serial", |b| {
+        b.iter(|| find_uncovered_by_scan(black_box(&sensors), min, max))
+    });
+    for threads in thread_counts {
+        group.bench_function(format!("parallel_{threads}"), |b| {
+            b.iter(|| find_uncovered_by_scan_parallel(black_box(&sensors), min, max, threads))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_scan);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+
+use advent_of_code_2022_rust::packet::{compare_packets, explain_order, try_parse_packet};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let line = String::from_utf8_lossy(data);
+    if let Ok(p) = try_parse_packet(&line) {
+        assert_eq!(Ok(&p), try_parse_packet(&p.to_string()).as_ref());
+        assert_eq!(None, compare_packets(&p, &p));
+        assert_eq!(None, explain_order(&p, &p).in_order);
+    }
+});
@@ -1,102 +1,119 @@
-use std::{cmp::Ordering, fs};
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum Packet {
-    Value(u8),
-    List(Vec<Packet>),
-}
-
-impl PartialOrd for Packet {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if *self == *other {
-            return Some(Ordering::Equal);
+use advent_of_code_2022_rust::packet::{
+    Packet, compare_packets, parse_packet, parse_packet_line, rank_of, rank_of_stream,
+    sum_ordered_pairs,
+};
+use std::{
+    fs::{self, File},
+    io::BufReader,
+};
+
+/// Pairs up the non-empty lines in order, the same way `sum_ordered_pairs`
+/// does, so blank lines between pairs don't have to be exact.
+fn part13(input: &str) -> usize {
+    let lines = input.lines().filter(|l| !l.is_empty()).collect::<Vec<_>>();
+    let chunks = lines.chunks(2);
+    let mut index_sum = 0;
+    for (index, chunk) in chunks.enumerate() {
+        let [line1, line2] = chunk else {
+            panic!("Pair {} has only one packet", index + 1);
+        };
+        let chars1: Vec<char> = line1.chars().collect();
+        let chars2: Vec<char> = line2.chars().collect();
+        let (packet1, rest1) = parse_packet(&chars1);
+        let (packet2, rest2) = parse_packet(&chars2);
+        if !rest1.is_empty() || !rest2.is_empty() {
+            panic!("Expect no rest");
         }
-        if let Some(b) = compare_packets(self, other) {
-            if b {
-                Some(Ordering::Less)
-            } else {
-                Some(Ordering::Greater)
-            }
-        } else {
-            None
+
+        let r = compare_packets(&packet1, &packet2).expect("Cannot determine");
+        if r {
+            index_sum += index + 1;
         }
     }
+    index_sum
 }
 
-fn parse_packet(s: &[char]) -> (Packet, &[char]) {
-    if s.is_empty() {
-        panic!("empty string")
-    }
-    let mut s = s;
-    if let ['[', rest @ ..] = s {
-        let mut v = Vec::new();
-        s = rest;
-        loop {
-            if !s.is_empty() && s[0] != ']' {
-                let (p, rest) = parse_packet(s);
-                v.push(p);
-                s = rest;
-            }
-            match s {
-                rest @ [] => {
-                    s = rest;
-                    break;
-                }
-                [',', rest @ ..] => {
-                    s = rest;
-                }
-                [']', rest @ ..] => {
-                    s = rest;
-                    break;
-                }
-                _ => panic!("Rest is not empty"),
-            }
-        }
-        (Packet::List(v), s)
-    } else {
-        let mut consumed = 0;
-        for (i, c) in s.iter().enumerate() {
-            if c.is_numeric() {
-                consumed = i + 1;
-            } else {
-                break;
-            }
-        }
-        let number_chars: String = s[..consumed].iter().collect();
-        let n = number_chars.parse::<u8>().unwrap();
+fn default_dividers() -> Vec<Packet> {
+    vec![parse_packet_line("[[2]]"), parse_packet_line("[[6]]")]
+}
 
-        (Packet::Value(n), &s[consumed..])
-    }
+fn part13_2(input: &str, dividers: &[Packet]) -> usize {
+    let packages = input
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(parse_packet_line)
+        .collect::<Vec<_>>();
+    rank_of(&packages, dividers).iter().product()
 }
 
-fn compare_packets(p1: &Packet, p2: &Packet) -> Option<bool> {
-    match (p1, p2) {
-        (Packet::Value(n1), Packet::Value(n2)) => {
-            if n1 == n2 {
-                None
-            } else {
-                Some(n1 < n2)
-            }
+#[cfg(feature = "json")]
+fn sort_json_lines(input: &str) -> Vec<String> {
+    let mut packets = input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| {
+            serde_json::from_str::<serde_json::Value>(l)
+                .map_err(|e| e.to_string())
+                .and_then(|v| Packet::try_from(&v))
+                .unwrap_or_else(|e| panic!("Line {}: {e}", i + 1))
+        })
+        .collect::<Vec<_>>();
+    packets.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    packets
+        .iter()
+        .map(|p| serde_json::Value::from(p).to_string())
+        .collect()
+}
+
+fn main() {
+    let mut stream = false;
+    let mut json_sort = false;
+    let mut positional = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--stream" => stream = true,
+            "--json-sort" => json_sort = true,
+            _ => positional.push(arg),
         }
-        (Packet::List(l1), Packet::List(l2)) => match (l1.as_slice(), l2.as_slice()) {
-            (&[], &[_, ..]) => Some(true),
-            ([_, ..], []) => Some(false),
-            ([], []) => None,
-            ([p1, l1new @ ..], [p2, l2new @ ..]) => {
-                if let Some(r) = compare_packets(p1, p2) {
-                    Some(r)
-                } else {
-                    compare_packets(&Packet::List(l1new.to_vec()), &Packet::List(l2new.to_vec()))
-                }
+    }
+    let mut positional = positional.into_iter();
+    let filename = positional
+        .next()
+        .unwrap_or_else(|| "input13.txt".to_string());
+    if json_sort {
+        #[cfg(feature = "json")]
+        {
+            let input = fs::read_to_string(filename).expect("Failed to read input file");
+            for line in sort_json_lines(&input) {
+                println!("{line}");
             }
-        },
-        (&Packet::Value(v1), &Packet::List(_)) => {
-            compare_packets(&Packet::List(vec![Packet::Value(v1)]), p2)
-        }
-        (&Packet::List(_), &Packet::Value(v2)) => {
-            compare_packets(p1, &Packet::List(vec![Packet::Value(v2)]))
+            return;
         }
+        #[cfg(not(feature = "json"))]
+        panic!("--json-sort requires the json feature");
     }
+    let dividers = positional
+        .map(|a| parse_packet_line(&a))
+        .collect::<Vec<_>>();
+    let dividers = if dividers.is_empty() {
+        default_dividers()
+    } else {
+        dividers
+    };
+    if stream {
+        let open = || BufReader::new(File::open(&filename).expect("Failed to read input file"));
+        let r1 = sum_ordered_pairs(open()).unwrap();
+        println!("Part 1: {r1}");
+        let r2: usize = rank_of_stream(open(), &dividers).unwrap().iter().product();
+        println!("Part 2: {r2}");
+        return;
+    }
+    let input = fs::read_to_string(filename).unwrap();
+    let r1 = part13(&input);
+    println!("Part 1: {r1}");
+    let r2 = part13_2(&input, &dividers);
+    println!("Part 2: {r2}");
 }
 
 #[cfg(test)]
@@ -134,65 +151,41 @@ mod tests {
 
     #[test]
     fn day13_part2_test1() {
-        assert_eq!(140, part13_2(INPUT_EX1));
+        assert_eq!(140, part13_2(INPUT_EX1, &default_dividers()));
     }
-}
-
-fn part13(input: &str) -> usize {
-    let lines = input.lines().collect::<Vec<_>>();
-    let chunks = lines.chunks(3);
-    let mut index_sum = 0;
-    for (index, chunk) in chunks.enumerate() {
-        let chars1: Vec<char> = chunk[0].chars().collect();
-        let chars2: Vec<char> = chunk[1].chars().collect();
-        let (packet1, rest1) = parse_packet(&chars1);
-        let (packet2, rest2) = parse_packet(&chars2);
-        if !rest1.is_empty() || !rest2.is_empty() {
-            panic!("Expect no rest");
-        }
 
-        let r = compare_packets(&packet1, &packet2).expect("Cannot determine");
-        if r {
-            index_sum += index + 1;
-        }
+    #[test]
+    fn day13_part2_custom_dividers() {
+        let dividers = [
+            parse_packet_line("[[2]]"),
+            parse_packet_line("[[6]]"),
+            parse_packet_line("[10]"),
+        ];
+        assert_eq!(140 * 19, part13_2(INPUT_EX1, &dividers));
     }
-    index_sum
-}
 
-fn part13_2(input: &str) -> usize {
-    let mut packages = input
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|l| {
-            let (p, _) = parse_packet(l.chars().collect::<Vec<char>>().as_slice());
-            p
-        })
-        .collect::<Vec<_>>();
-    let divider_package_1 = Packet::List(vec![Packet::List(vec![Packet::Value(2)])]);
-    let divider_package_2 = Packet::List(vec![Packet::List(vec![Packet::Value(6)])]);
-    packages.push(divider_package_1.clone());
-    packages.push(divider_package_2.clone());
+    #[test]
+    fn day13_streaming() {
+        assert_eq!(13, sum_ordered_pairs(INPUT_EX1.as_bytes()).unwrap());
+        let ranks = rank_of_stream(INPUT_EX1.as_bytes(), &default_dividers()).unwrap();
+        assert_eq!(vec![10, 14], ranks);
+    }
 
-    packages.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-    let i1 = 1 + packages
-        .iter()
-        .enumerate()
-        .find(|(_, p)| **p == divider_package_1)
-        .unwrap()
-        .0;
-    let i2 = 1 + packages
-        .iter()
-        .enumerate()
-        .find(|(_, p)| **p == divider_package_2)
-        .unwrap()
-        .0;
-    i1 * i2
-}
+    #[test]
+    fn day13_streaming_irregular_blank_lines() {
+        // Missing and doubled blank lines between pairs, and trailing ones.
+        let input = "[1,1,3,1,1]\n[1,1,5,1,1]\n[[1],[2,3,4]]\n[[1],4]\n\n\n[9]\n[[8,7,6]]\n\n\n\n[]\n[3]\n\n";
+        assert_eq!(1 + 2 + 4, part13(input));
+        assert_eq!(part13(input), sum_ordered_pairs(input.as_bytes()).unwrap());
+    }
 
-fn main() {
-    let input = fs::read_to_string("input13.txt").unwrap();
-    let r1 = part13(&input);
-    println!("Part 1: {r1}");
-    let r2 = part13_2(&input);
-    println!("Part 2: {r2}");
+    #[cfg(feature = "json")]
+    #[test]
+    fn day13_json_sort() {
+        let input = "[[6]]\n[1, [2, 3]]\n\n[]\n[[2]]\n";
+        assert_eq!(
+            vec!["[]", "[1,[2,3]]", "[[2]]", "[[6]]"],
+            sort_json_lines(input)
+        );
+    }
 }
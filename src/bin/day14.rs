@@ -1,102 +1,122 @@
-use regex::Regex;
-use std::collections::HashSet;
-use std::fs;
+use advent_of_code_2022_rust::cave::{
+    Cave, CaveConfig, CaveGrid, Particle, SimulationMode, count_with_floor_fast, parse_paths,
+};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+};
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_day14a() {
-        let input = r#"498,4 -> 498,6 -> 496,6
-503,4 -> 502,4 -> 502,9 -> 494,9"#;
-        let r = day14(input, false);
-        assert_eq!(r, 24);
-    }
-
-    #[test]
-    fn test_day14b() {
-        let input = r#"498,4 -> 498,6 -> 496,6
-503,4 -> 502,4 -> 502,9 -> 494,9"#;
-        let r = day14(input, true);
-        assert_eq!(r, 93);
-    }
+fn day14(paths: &[Vec<(i64, i64)>], mode: SimulationMode) -> Cave {
+    let config = CaveConfig {
+        mode,
+        ..Default::default()
+    };
+    let obstacles = CaveGrid::from_paths(paths, &config);
+    Cave::new(obstacles, &config)
 }
 
-fn day14(input: &str, part2: bool) -> usize {
-    let re = Regex::new(r"(\d+),(\d+)").unwrap();
-    let paths = input
-        .lines()
-        .map(|line| {
-            line.split(" -> ")
-                .map(|pointsstr| {
-                    let captures = re.captures(pointsstr).unwrap();
-                    let (_, [x, y]) = captures.extract();
-                    (x.parse::<usize>().unwrap(), y.parse::<usize>().unwrap())
-                })
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
+fn day14_fast(paths: &[Vec<(i64, i64)>]) -> usize {
+    let config = CaveConfig {
+        mode: SimulationMode::Floor { depth: 2 },
+        ..Default::default()
+    };
+    let obstacles = CaveGrid::from_paths(paths, &config);
+    count_with_floor_fast(&obstacles, &config)
+}
 
-    let mut obstacles = HashSet::new();
-    paths.iter().for_each(|points| {
-        let mut iter = points.iter();
-        let mut p = *iter.next().unwrap();
-        for p2 in iter {
-            obstacles.insert(p);
-            while p != *p2 {
-                if p.0 == p2.0 {
-                    p.1 = if p2.1 > p.1 { p.1 + 1 } else { p.1 - 1 };
-                } else {
-                    p.0 = if p2.0 > p.0 { p.0 + 1 } else { p.0 - 1 };
-                }
-                obstacles.insert(p);
+fn main() {
+    let mut filename = "input14.txt".to_string();
+    let mut render = false;
+    let mut fast = false;
+    let mut ppm_prefix = None;
+    let mut frames = None;
+    let mut water = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render" => render = true,
+            "--fast" => fast = true,
+            "--water" => water = true,
+            "--ppm" => ppm_prefix = Some(args.next().expect("--ppm needs a file prefix")),
+            "--frames" => {
+                let dir = args.next().expect("--frames needs a directory");
+                let every = args
+                    .next()
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .expect("--frames needs a grain interval");
+                frames = Some((PathBuf::from(dir), every));
             }
+            _ => filename = arg,
         }
-    });
+    }
+    let input = fs::read_to_string(filename).expect("Failed to read input file");
+    let paths = match parse_paths(&input) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
-    let max_y = obstacles.iter().map(|p| p.1).max().unwrap();
+    if water {
+        let config = CaveConfig {
+            particle: Particle::Water,
+            ..Default::default()
+        };
+        let mut cave = Cave::new(CaveGrid::from_paths(&paths, &config), &config);
+        println!("Day 14 water: {}", cave.drop_until_stable().grains);
+        if render {
+            print!("{}", cave.render());
+        }
+        return;
+    }
 
-    let mut drop_sand = |p: (usize, usize)| -> bool {
-        let mut p = p;
-        while p.1 <= max_y {
-            if !obstacles.contains(&(p.0, p.1 + 1)) {
-                p = (p.0, p.1 + 1);
-            } else if !obstacles.contains(&(p.0 - 1, p.1 + 1)) {
-                p = (p.0 - 1, p.1 + 1);
-            } else if !obstacles.contains(&(p.0 + 1, p.1 + 1)) {
-                p = (p.0 + 1, p.1 + 1);
-            } else {
-                obstacles.insert(p);
-                if part2 && p == (500, 0) {
-                    return false;
-                }
-                return true;
-            }
+    let modes = [SimulationMode::Abyss, SimulationMode::Floor { depth: 2 }];
+    for (part, mode) in (1..).zip(modes) {
+        if fast && part == 2 {
+            println!("Day 14 part 2: {}", day14_fast(&paths));
+            continue;
         }
-        if part2 {
-            obstacles.insert(p);
-            true
-        } else {
-            false
+        let mut cave = day14(&paths, mode);
+        if let Some((dir, every)) = &frames {
+            cave.dump_frames(&dir.join(format!("part{part}")), *every, 2)
+                .expect("Failed to write frames");
+        }
+        let r = cave.drop_until_stable();
+        println!("Day 14 part {part}: {}", r.grains);
+        if render {
+            print!("{}", cave.render());
+        }
+        if let Some(prefix) = &ppm_prefix {
+            let file = File::create(format!("{prefix}{part}.ppm")).expect("Failed to create image");
+            cave.write_ppm(&mut BufWriter::new(file), 4)
+                .expect("Failed to write image");
         }
-    };
-
-    let mut count = 0;
-    while drop_sand((500, 0)) {
-        count += 1;
     }
-    if part2 { count + 1 } else { count }
 }
 
-fn main() {
-    let input = if let Some(filename) = std::env::args().nth(1) {
-        fs::read_to_string(filename).expect("Failed to read input file")
-    } else {
-        fs::read_to_string("input14.txt").expect("Failed to read input file")
-    };
-    let r1 = day14(&input, false);
-    println!("Day 14 part 1: {r1}");
-    let r2 = day14(&input, true);
-    println!("Day 14 part 2: {r2}");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+    #[test]
+    fn test_day14a() {
+        let paths = parse_paths(EXAMPLE).unwrap();
+        let r = day14(&paths, SimulationMode::Abyss).drop_until_stable();
+        assert_eq!(r.grains, 24);
+        assert!(r.fell_into_abyss);
+    }
+
+    #[test]
+    fn test_day14b() {
+        let paths = parse_paths(EXAMPLE).unwrap();
+        let r = day14(&paths, SimulationMode::Floor { depth: 2 }).drop_until_stable();
+        assert_eq!(r.grains, 93);
+        assert!(r.source_blocked);
+        assert_eq!(day14_fast(&paths), 93);
+    }
 }
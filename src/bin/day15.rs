@@ -1,5 +1,252 @@
-use advent_of_code_2022_rust::parse_utils;
-use std::{collections::HashSet, fs, ops::Add};
+use advent_of_code_2022_rust::sensor::{
+    BeaconSearch, Manhattan, Point, Rect, Sensor, SensorField, count_excluded, covered_area,
+    find_uncovered_by_boundaries, find_uncovered_by_rotation, find_uncovered_by_scan,
+    find_uncovered_by_scan_parallel, parse_sensors, render_coverage, uncovered_regions,
+    write_coverage_ppm,
+};
+use advent_of_code_2022_rust::sensor3d::{self, Point3};
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::BufWriter,
+};
+
+/// Whether a beacon is known not to be at `p`: some sensor covers it and it
+/// is not one of the known beacons, whichever sensor reported it.
+fn is_pos_clear(field: &SensorField<Manhattan>, beacons: &HashSet<Point>, p: &Point) -> bool {
+    !beacons.contains(p) && field.is_covered(p)
+}
+
+fn solve(input_file: &str, y: i64) -> usize {
+    count_excluded(&parse_sensors(input_file), y)
+}
+
+/// Checks every cell of the row one by one; kept to cross-check `solve`.
+fn solve_brute(input_file: &str, y: i64) -> usize {
+    let sensors = parse_sensors(input_file);
+    let beacons = sensors.iter().map(|s| s.beacon).collect::<HashSet<_>>();
+    let max_distance = sensors.iter().map(Sensor::radius).max().unwrap();
+    let all_points1 = sensors.iter().map(|s| s.pos).collect::<Vec<_>>();
+    let all_points2 = sensors.iter().map(|s| s.beacon).collect::<Vec<_>>();
+    let all_points = all_points1
+        .iter()
+        .chain(all_points2.iter())
+        .collect::<Vec<_>>();
+    let min_pos_x = all_points.iter().map(|p| p.x).min().unwrap();
+    let max_pos_x = all_points.iter().map(|p| p.x).max().unwrap();
+    let field = SensorField::new(&sensors, Manhattan);
+    let mut line_cleared = HashSet::new();
+    for x in min_pos_x - max_distance..=max_pos_x + max_distance {
+        if is_pos_clear(&field, &beacons, &Point::new(x, y)) {
+            line_cleared.insert(x);
+        }
+    }
+    line_cleared.len()
+}
+
+fn no_solution(search: &BeaconSearch) -> String {
+    format!(
+        "No uncovered position in x={}..={}, y={}..={}",
+        search.x_bounds.0, search.x_bounds.1, search.y_bounds.0, search.y_bounds.1
+    )
+}
+
+fn solve2(input_file: &str, search: &BeaconSearch) -> Result<Point, String> {
+    let sensors = parse_sensors(input_file);
+    find_uncovered_by_scan(&sensors, search.min(), search.max()).ok_or_else(|| no_solution(search))
+}
+
+fn solve2_parallel(
+    input_file: &str,
+    search: &BeaconSearch,
+    threads: usize,
+) -> Result<Point, String> {
+    let sensors = parse_sensors(input_file);
+    find_uncovered_by_scan_parallel(&sensors, search.min(), search.max(), threads)
+        .ok_or_else(|| no_solution(search))
+}
+
+fn solve2_boundaries(input_file: &str, search: &BeaconSearch) -> Result<Point, String> {
+    let sensors = parse_sensors(input_file);
+    find_uncovered_by_boundaries(&sensors, search.min(), search.max())
+        .ok_or_else(|| no_solution(search))
+}
+
+fn solve2_rotated(input_file: &str, search: &BeaconSearch) -> Result<Point, String> {
+    let sensors = parse_sensors(input_file);
+    find_uncovered_by_rotation(&sensors, search.min(), search.max())
+        .ok_or_else(|| no_solution(search))
+}
+
+/// Every uncovered region in the search box; an error if there is none.
+fn solve2_all(input_file: &str, search: &BeaconSearch) -> Result<Vec<Rect>, String> {
+    let sensors = parse_sensors(input_file);
+    let regions = uncovered_regions(&sensors, search.min(), search.max());
+    if regions.is_empty() {
+        return Err(no_solution(search));
+    }
+    Ok(regions)
+}
+
+/// Finds a gap among sensors given with `z=` coordinates, searching the
+/// x and y bounds of `search` and `z_bounds`.
+fn solve_3d(
+    input_file: &str,
+    search: &BeaconSearch,
+    z_bounds: (i64, i64),
+) -> Result<Point3, String> {
+    let sensors = sensor3d::parse_sensors(input_file);
+    let min = Point3::new(search.x_bounds.0, search.y_bounds.0, z_bounds.0);
+    let max = Point3::new(search.x_bounds.1, search.y_bounds.1, z_bounds.1);
+    sensor3d::find_uncovered_by_scan(&sensors, min, max)
+        .ok_or_else(|| format!("{}, z={}..={}", no_solution(search), z_bounds.0, z_bounds.1))
+}
+
+fn parse_arg(args: &mut impl Iterator<Item = String>, flag: &str) -> i64 {
+    args.next()
+        .and_then(|a| a.parse().ok())
+        .unwrap_or_else(|| panic!("{flag} needs a number"))
+}
+
+fn parse_positive(args: &mut impl Iterator<Item = String>, flag: &str) -> usize {
+    args.next()
+        .and_then(|a| a.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| panic!("{flag} needs a positive number"))
+}
+
+fn main() {
+    let mut filename = "input15.txt".to_string();
+    let mut brute = false;
+    let mut boundaries = false;
+    let mut all = false;
+    let mut rotated = false;
+    let mut area = false;
+    let mut threads = None;
+    let mut window = None;
+    let mut ppm = None;
+    let mut three_d = false;
+    let mut z_bounds = None;
+    let mut search = BeaconSearch::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--brute" => brute = true,
+            "--boundaries" => boundaries = true,
+            "--rotated" => rotated = true,
+            "--area" => area = true,
+            "--threads" => threads = Some(parse_positive(&mut args, "--threads")),
+            "--all" => all = true,
+            "--3d" => three_d = true,
+            "--z" => {
+                z_bounds = Some((parse_arg(&mut args, "--z"), parse_arg(&mut args, "--z")));
+            }
+            "--render" => {
+                let mut corner = || {
+                    let x = parse_arg(&mut args, "--render");
+                    Point::new(x, parse_arg(&mut args, "--render"))
+                };
+                window = Some((corner(), corner()));
+            }
+            "--ppm" => {
+                let file = args.next().expect("--ppm needs a file name");
+                ppm = Some((file, parse_positive(&mut args, "--ppm")));
+            }
+            "--example" => search = BeaconSearch::example(),
+            "--row" => search.target_row = parse_arg(&mut args, "--row"),
+            "--max" => {
+                let max = parse_arg(&mut args, "--max");
+                search.x_bounds = (0, max);
+                search.y_bounds = (0, max);
+            }
+            "--x" => {
+                search.x_bounds = (parse_arg(&mut args, "--x"), parse_arg(&mut args, "--x"));
+            }
+            "--y" => {
+                search.y_bounds = (parse_arg(&mut args, "--y"), parse_arg(&mut args, "--y"));
+            }
+            "--multiplier" => search.tuning_multiplier = parse_arg(&mut args, "--multiplier"),
+            _ => filename = arg,
+        }
+    }
+    let input_file = fs::read_to_string(filename).expect("Failed to read input file");
+
+    if three_d {
+        let z_bounds = z_bounds.unwrap_or(search.y_bounds);
+        match solve_3d(&input_file, &search, z_bounds) {
+            Ok(p) => println!("Uncovered: x={}, y={}, z={}", p.x, p.y, p.z),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let r = if brute {
+        solve_brute(&input_file, search.target_row)
+    } else {
+        solve(&input_file, search.target_row)
+    };
+    println!("{}", r);
+
+    let gap = if all {
+        solve2_all(&input_file, &search).map(|regions| {
+            let cells = regions.iter().map(Rect::area).sum::<i64>();
+            if cells > 1 {
+                eprintln!("Warning: {cells} uncovered positions, the beacon is not unique");
+            }
+            for r in &regions {
+                println!(
+                    "Uncovered: x={}..={}, y={}..={}",
+                    r.min.x, r.max.x, r.min.y, r.max.y
+                );
+            }
+            regions[0].min
+        })
+    } else if let Some(threads) = threads {
+        solve2_parallel(&input_file, &search, threads)
+    } else if rotated {
+        solve2_rotated(&input_file, &search)
+    } else if boundaries {
+        solve2_boundaries(&input_file, &search)
+    } else {
+        solve2(&input_file, &search)
+    };
+    if area {
+        let (min, max) = (search.min(), search.max());
+        let covered = covered_area(&parse_sensors(&input_file), min, max);
+        let total = (max.x - min.x + 1) * (max.y - min.y + 1);
+        println!("Covered: {covered} of {total}");
+    }
+    if let Some((min, max)) = window {
+        print!("{}", render_coverage(&parse_sensors(&input_file), min, max));
+    }
+    if let Some((file, size)) = ppm {
+        let sensors = parse_sensors(&input_file);
+        let mark = gap.as_ref().ok().copied();
+        let file = File::create(file).expect("Failed to create image");
+        write_coverage_ppm(
+            &mut BufWriter::new(file),
+            &sensors,
+            search.min(),
+            search.max(),
+            size,
+            mark,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Failed to write image: {e}");
+            std::process::exit(1);
+        });
+    }
+    match gap {
+        Ok(p) => println!("{}", search.tuning_frequency(&p)),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -28,129 +275,115 @@ Sensor at x=20, y=1: closest beacon is at x=15, y=3
     }
 
     #[test]
-    pub fn test2() {
-        let r = solve2(EXAMPLE1_INPUT, 20);
-        assert_eq!(56000011, r);
-    }
-}
-
-#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
-struct Point {
-    x: i64,
-    y: i64,
-}
-
-impl Point {
-    fn new(x: i64, y: i64) -> Self {
-        Point { x, y }
-    }
-}
-
-impl Add for &Point {
-    type Output = Point;
-
-    fn add(self, other: Self) -> Self::Output {
-        Point {
-            x: self.x + other.x,
-            y: self.y + other.y,
+    pub fn test1_brute() {
+        for y in -5..30 {
+            assert_eq!(
+                solve_brute(EXAMPLE1_INPUT, y),
+                solve(EXAMPLE1_INPUT, y),
+                "row {y}"
+            );
         }
     }
-}
 
-fn manhattan_distance(p1: &Point, p2: &Point) -> i64 {
-    (p1.x - p2.x).abs() + (p1.y - p2.y).abs()
-}
-
-fn parse(input_file: &str) -> Vec<(Point, Point)> {
-    let xss = input_file
-        .lines()
-        .map(parse_utils::parse_signed_numbers)
-        .collect::<Vec<_>>();
-    let mut sensors = Vec::new();
-    for xs in xss.into_iter() {
-        if let [x1, y1, x2, y2] = xs.as_slice() {
-            sensors.push((Point::new(*x1, *y1), Point::new(*x2, *y2)));
-        }
+    #[test]
+    pub fn test1_beacon_of_other_sensor() {
+        // The second sensor's beacon lies exactly on the first sensor's
+        // perimeter, so the first sensor alone would call that cell clear.
+        let input = "Sensor at x=0, y=0: closest beacon is at x=2, y=0
+Sensor at x=0, y=3: closest beacon is at x=0, y=2";
+        assert_eq!(0, solve_brute(input, 2));
+        assert_eq!(0, solve(input, 2));
+        assert_eq!(4, solve_brute(input, 0));
     }
-    sensors
-}
 
-fn is_pos_clear(sensors: &Vec<(Point, Point)>, p: &Point) -> bool {
-    for (s1, s2) in sensors {
-        if p != s2 && manhattan_distance(p, s1) <= manhattan_distance(s1, s2) {
-            return true;
-        }
+    #[test]
+    pub fn test2() {
+        let r = solve2(EXAMPLE1_INPUT, &BeaconSearch::example()).unwrap();
+        assert_eq!(56000011, BeaconSearch::example().tuning_frequency(&r));
+        assert_eq!(
+            Ok(r),
+            solve2_boundaries(EXAMPLE1_INPUT, &BeaconSearch::example())
+        );
+        assert_eq!(
+            Ok(r),
+            solve2_rotated(EXAMPLE1_INPUT, &BeaconSearch::example())
+        );
+        assert_eq!(
+            Ok(r),
+            solve2_parallel(EXAMPLE1_INPUT, &BeaconSearch::example(), 4)
+        );
+        let regions = solve2_all(EXAMPLE1_INPUT, &BeaconSearch::example()).unwrap();
+        assert_eq!(
+            vec![Rect {
+                min: Point::new(14, 11),
+                max: Point::new(14, 11)
+            }],
+            regions
+        );
     }
-    false
-}
 
-fn solve(input_file: &str, y: i64) -> usize {
-    let sensors = parse(input_file);
-    let max_distance = sensors
-        .iter()
-        .map(|(p1, p2)| manhattan_distance(p1, p2))
-        .max()
-        .unwrap();
-    let all_points1 = sensors
-        .iter()
-        .cloned()
-        .map(|(p1, _)| p1)
-        .collect::<Vec<_>>();
-    let all_points2 = sensors
-        .iter()
-        .cloned()
-        .map(|(_, p2)| p2)
-        .collect::<Vec<_>>();
-    let all_points = all_points1
-        .iter()
-        .chain(all_points2.iter())
-        .collect::<Vec<_>>();
-    let min_pos_x = all_points.iter().map(|p| p.x).min().unwrap();
-    let max_pos_x = all_points.iter().map(|p| p.x).max().unwrap();
-    let mut line_cleared = HashSet::new();
-    for x in min_pos_x - max_distance..=max_pos_x + max_distance {
-        if is_pos_clear(&sensors, &Point::new(x, y)) {
-            line_cleared.insert(x);
-        }
+    #[test]
+    pub fn test2_covered_area() {
+        let search = BeaconSearch::example();
+        let sensors = parse_sensors(EXAMPLE1_INPUT);
+        assert_eq!(
+            21 * 21 - 1,
+            covered_area(&sensors, search.min(), search.max())
+        );
     }
-    line_cleared.len()
-}
 
-fn is_pos_clear2(sensors: &Vec<(Point, Point)>, p: &Point) -> Option<i64> {
-    for (s1, s2) in sensors {
-        let d = manhattan_distance(s1, s2) - manhattan_distance(p, s1);
-        if d >= 0 {
-            return Some(d);
-        }
+    #[test]
+    pub fn test2_3d() {
+        // The example lifted into the plane z=0. One layer up every sensor
+        // reaches one step less, so gaps open up there too.
+        let input = EXAMPLE1_INPUT
+            .lines()
+            .map(|l| l.replace(": ", ", z=0: ") + ", z=0")
+            .collect::<Vec<_>>()
+            .join("\n");
+        let search = BeaconSearch::example();
+        let gap = Point3::new(14, 11, 0);
+        assert_eq!(Ok(gap), solve_3d(&input, &search, (0, 0)));
+        let above = solve_3d(&input, &search, (1, 1)).unwrap();
+        assert_eq!(1, above.z);
+        assert_eq!(
+            Err("No uncovered position in x=0..=13, y=0..=20, z=0..=0".to_string()),
+            solve_3d(
+                &input,
+                &BeaconSearch {
+                    x_bounds: (0, 13),
+                    ..search
+                },
+                (0, 0)
+            )
+        );
     }
-    None
-}
 
-fn solve2(input_file: &str, r: i64) -> i64 {
-    let sensors = parse(input_file);
-    for y in 0..=r {
-        let mut x = 0;
-        loop {
-            let d = is_pos_clear2(&sensors, &Point::new(x, y));
-            if let Some(d) = d {
-                x += d + 1;
-                if x > r {
-                    break;
-                }
-            } else {
-                return 4000000 * x + y;
-            }
-        }
+    #[test]
+    pub fn test2_no_solution() {
+        let search = BeaconSearch {
+            x_bounds: (0, 13),
+            ..BeaconSearch::example()
+        };
+        let err = "No uncovered position in x=0..=13, y=0..=20".to_string();
+        assert_eq!(Err(err.clone()), solve2(EXAMPLE1_INPUT, &search));
+        assert_eq!(Err(err.clone()), solve2_boundaries(EXAMPLE1_INPUT, &search));
+        assert_eq!(Err(err.clone()), solve2_rotated(EXAMPLE1_INPUT, &search));
+        assert_eq!(Err(err), solve2_all(EXAMPLE1_INPUT, &search));
     }
-    panic!("No solution found");
-}
 
-fn main() {
-    let input_file = fs::read_to_string("input15.txt").unwrap();
-
-    let r = solve(&input_file, 2000000);
-    println!("{}", r);
-
-    let r = solve2(&input_file, 4000000);
-    println!("{}", r);
+    #[test]
+    pub fn test2_custom_search() {
+        // A narrower x range that still holds the beacon at (14, 11).
+        let search = BeaconSearch {
+            x_bounds: (10, 15),
+            tuning_multiplier: 100,
+            ..BeaconSearch::example()
+        };
+        let gap = Point::new(14, 11);
+        assert_eq!(Ok(gap), solve2(EXAMPLE1_INPUT, &search));
+        assert_eq!(Ok(gap), solve2_boundaries(EXAMPLE1_INPUT, &search));
+        assert_eq!(Ok(gap), solve2_rotated(EXAMPLE1_INPUT, &search));
+        assert_eq!(1411, search.tuning_frequency(&gap));
+    }
 }
@@ -4,35 +4,6 @@ use std::{
     fs,
 };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    const EXAMPLE1_INPUT: &str = r"Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
-Valve BB has flow rate=13; tunnels lead to valves CC, AA
-Valve CC has flow rate=2; tunnels lead to valves DD, BB
-Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
-Valve EE has flow rate=3; tunnels lead to valves FF, DD
-Valve FF has flow rate=0; tunnels lead to valves EE, GG
-Valve GG has flow rate=0; tunnels lead to valves FF, HH
-Valve HH has flow rate=22; tunnel leads to valve GG
-Valve II has flow rate=0; tunnels lead to valves AA, JJ
-Valve JJ has flow rate=21; tunnel leads to valve II
-";
-
-    #[test]
-    pub fn test1() {
-        let r = solve(EXAMPLE1_INPUT);
-        assert_eq!(1651, r);
-    }
-
-    #[test]
-    pub fn test2() {
-        let r = solve2(EXAMPLE1_INPUT);
-        assert_eq!(1707, r);
-    }
-}
-
 fn parse(input_file: &str) -> HashMap<String, (usize, Vec<String>)> {
     let rgx = Regex::new(r"Valve (?P<name>[A-Z]{2}) has flow rate=(?P<flow>[0-9]+); tunnels? leads? to valves? (?P<tunnels>[A-Z, ]{2,})").unwrap();
     let mut report = HashMap::new();
@@ -207,3 +178,32 @@ fn main() {
     let r = solve2(&input_file);
     println!("Part 2: {}", r);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE1_INPUT: &str = r"Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II
+";
+
+    #[test]
+    pub fn test1() {
+        let r = solve(EXAMPLE1_INPUT);
+        assert_eq!(1651, r);
+    }
+
+    #[test]
+    pub fn test2() {
+        let r = solve2(EXAMPLE1_INPUT);
+        assert_eq!(1707, r);
+    }
+}
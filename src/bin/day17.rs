@@ -1,28 +1,5 @@
 use std::fs;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    const EXAMPLE1_INPUT: &str = r">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
-    #[allow(dead_code)]
-    const EXAMPLE2_INPUT: &str = r"";
-    #[allow(dead_code)]
-    const EXAMPLE3_INPUT: &str = r"";
-
-    #[test]
-    pub fn test1() {
-        let r = solve(EXAMPLE1_INPUT);
-        assert_eq!(3068, r);
-    }
-
-    #[test]
-    pub fn test2() {
-        let r = solve2(EXAMPLE1_INPUT);
-        assert_eq!(1514285714288, r);
-    }
-}
-
 const SHAPES: [&str; 5] = [
     r"####",
     r".#.
@@ -93,7 +70,10 @@ fn write_chamber_at(chamber: &mut Chamber, x: i32, y: i32, value: char) {
 fn print_chamber(chamber: &Chamber) {
     for y in 0..chamber_height(chamber) {
         print!("|");
-        for c in chamber[chamber.len() - 1 - y].iter().take(chamber_width(chamber)) {
+        for c in chamber[chamber.len() - 1 - y]
+            .iter()
+            .take(chamber_width(chamber))
+        {
             print!("{}", c);
         }
         println!("|");
@@ -148,7 +128,7 @@ fn drop_rock(
 ) -> usize {
     let mut x = x;
     let mut y = y;
-    if DRAWING_ENABLED >= 1 {
+    if DRAWING_ENABLED != 0 {
         println!("New rock");
         print_chamber_with_falling_rock(chamber, rock_shape, x, y);
     }
@@ -341,3 +321,26 @@ fn main() {
     let r = solve2(&input_file);
     println!("Part 2: {}", r);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE1_INPUT: &str = r">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
+    #[allow(dead_code)]
+    const EXAMPLE2_INPUT: &str = r"";
+    #[allow(dead_code)]
+    const EXAMPLE3_INPUT: &str = r"";
+
+    #[test]
+    pub fn test1() {
+        let r = solve(EXAMPLE1_INPUT);
+        assert_eq!(3068, r);
+    }
+
+    #[test]
+    pub fn test2() {
+        let r = solve2(EXAMPLE1_INPUT);
+        assert_eq!(1514285714288, r);
+    }
+}
@@ -0,0 +1,1329 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Puzzle rules: straight down, then down-left, then down-right.
+pub const DEFAULT_FALL_DIRECTIONS: [(i64, i64); 3] = [(0, 1), (-1, 1), (1, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationMode {
+    /// Sand below the lowest rock falls forever (part 1).
+    Abyss,
+    /// An infinite floor lies `depth` rows below the lowest rock (part 2 uses
+    /// a depth of 2).
+    Floor { depth: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Particle {
+    /// Falls along `CaveConfig::fall_directions`.
+    Sand,
+    /// Falls straight down and, when blocked, flows sideways towards the
+    /// nearest edge it can fall over, preferring the left on ties. Water that
+    /// has no edge to reach settles, so basins fill up row by row. Only works
+    /// with `SimulationMode::Abyss`, as water on an endless floor never stops.
+    Water,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaveConfig {
+    /// Points sand is poured from. Grains are dropped from each source in
+    /// turn until every source is blocked or drains into the abyss.
+    pub sources: Vec<(i64, i64)>,
+    pub mode: SimulationMode,
+    /// Moves a grain tries in order, as `(dx, dy)`. Every move must go down.
+    pub fall_directions: Vec<(i64, i64)>,
+    pub particle: Particle,
+}
+
+impl Default for CaveConfig {
+    fn default() -> Self {
+        CaveConfig {
+            sources: vec![(500, 0)],
+            mode: SimulationMode::Abyss,
+            fall_directions: DEFAULT_FALL_DIRECTIONS.to_vec(),
+            particle: Particle::Sand,
+        }
+    }
+}
+
+impl CaveConfig {
+    fn max_dx(&self) -> i64 {
+        self.fall_directions
+            .iter()
+            .map(|d| d.0.abs())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Parses rock paths, one `x,y -> x,y -> ...` path per line. Blank lines are
+/// skipped and a path with a single point is a single rock.
+pub fn parse_paths(input: &str) -> Result<Vec<Vec<(i64, i64)>>, String> {
+    let mut paths = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let points = line
+            .split("->")
+            .map(|point| {
+                let (x, y) = point
+                    .split_once(',')
+                    .ok_or_else(|| format!("Line {}: malformed point {point:?}", i + 1))?;
+                match (x.trim().parse::<i64>(), y.trim().parse::<i64>()) {
+                    (Ok(x), Ok(y)) => Ok((x, y)),
+                    _ => Err(format!("Line {}: malformed point {point:?}", i + 1)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(w) = points
+            .windows(2)
+            .find(|w| w[0].0 != w[1].0 && w[0].1 != w[1].1)
+        {
+            return Err(format!(
+                "Line {}: diagonal segment from {},{} to {},{}",
+                i + 1,
+                w[0].0,
+                w[0].1,
+                w[1].0,
+                w[1].1
+            ));
+        }
+        paths.push(points);
+    }
+    Ok(paths)
+}
+
+/// Dense occupancy bitmap for a cave slice, one bit per cell.
+///
+/// Cells outside the bounds read as free, so sand falling past the edges
+/// behaves like falling into the abyss.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaveGrid {
+    min: (i64, i64),
+    width: usize,
+    height: usize,
+    bits: Vec<u64>,
+}
+
+impl CaveGrid {
+    /// Creates an empty grid covering the box from `min` to `max` inclusive.
+    pub fn new(min: (i64, i64), max: (i64, i64)) -> Self {
+        let width = (max.0 - min.0 + 1) as usize;
+        let height = (max.1 - min.1 + 1) as usize;
+        CaveGrid {
+            min,
+            width,
+            height,
+            bits: vec![0; (width * height).div_ceil(64)],
+        }
+    }
+
+    /// Creates a grid with the rock paths drawn in, sized so that every grain
+    /// the simulation described by `config` can settle fits inside it.
+    ///
+    /// Panics if a path has a diagonal segment; `parse_paths` rejects those.
+    pub fn from_paths(paths: &[Vec<(i64, i64)>], config: &CaveConfig) -> Self {
+        let points = || paths.iter().flatten().chain(config.sources.iter());
+        let min_y = points().map(|p| p.1).min().unwrap_or(0);
+        let max_y = points().map(|p| p.1).max().unwrap_or(0);
+        let max_dx = config.max_dx();
+        // Every move goes down at least one row, so a grain takes at most
+        // `floor - source` moves and spreads at most `max_dx` per move.
+        let (max_y, spread) = match config.mode {
+            SimulationMode::Floor { depth } => {
+                let floor = max_y + depth as i64;
+                let spread = config
+                    .sources
+                    .iter()
+                    .map(|s| max_dx * (floor - s.1))
+                    .max()
+                    .unwrap_or(0);
+                (floor, spread)
+            }
+            SimulationMode::Abyss => (max_y, 0),
+        };
+        let min_x = config
+            .sources
+            .iter()
+            .map(|s| s.0 - spread)
+            .chain(points().map(|p| p.0))
+            .min()
+            .unwrap_or(0);
+        let max_x = config
+            .sources
+            .iter()
+            .map(|s| s.0 + spread)
+            .chain(points().map(|p| p.0))
+            .max()
+            .unwrap_or(0);
+        let mut grid = CaveGrid::new((min_x - max_dx, min_y), (max_x + max_dx, max_y));
+        for points in paths {
+            let mut iter = points.iter();
+            let Some(&first) = iter.next() else {
+                continue;
+            };
+            let mut p = first;
+            grid.insert(p);
+            for p2 in iter {
+                assert!(
+                    p.0 == p2.0 || p.1 == p2.1,
+                    "Diagonal segment from {p:?} to {p2:?}"
+                );
+                while p != *p2 {
+                    if p.0 == p2.0 {
+                        p.1 += (p2.1 - p.1).signum();
+                    } else {
+                        p.0 += (p2.0 - p.0).signum();
+                    }
+                    grid.insert(p);
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn min(&self) -> (i64, i64) {
+        self.min
+    }
+
+    pub fn max(&self) -> (i64, i64) {
+        (
+            self.min.0 + self.width as i64 - 1,
+            self.min.1 + self.height as i64 - 1,
+        )
+    }
+
+    /// All cells of the grid, row by row.
+    pub fn cells(&self) -> impl Iterator<Item = (i64, i64)> + use<> {
+        let (min, max) = (self.min, self.max());
+        (min.1..=max.1).flat_map(move |y| (min.0..=max.0).map(move |x| (x, y)))
+    }
+
+    fn index(&self, p: (i64, i64)) -> Option<usize> {
+        let x = usize::try_from(p.0 - self.min.0).ok()?;
+        let y = usize::try_from(p.1 - self.min.1).ok()?;
+        if x >= self.width || y >= self.height {
+            None
+        } else {
+            Some(y * self.width + x)
+        }
+    }
+
+    pub fn contains(&self, p: (i64, i64)) -> bool {
+        self.index(p)
+            .is_some_and(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    /// Marks a cell as occupied. Returns `false` if it already was.
+    ///
+    /// Panics if `p` is outside the grid.
+    pub fn insert(&mut self, p: (i64, i64)) -> bool {
+        let i = self
+            .index(p)
+            .unwrap_or_else(|| panic!("{p:?} is outside the cave grid"));
+        let was_set = self.bits[i / 64] & (1 << (i % 64)) != 0;
+        self.bits[i / 64] |= 1 << (i % 64);
+        !was_set
+    }
+
+    /// Marks a cell as free. Returns `false` if it already was.
+    pub fn remove(&mut self, p: (i64, i64)) -> bool {
+        let Some(i) = self.index(p) else {
+            return false;
+        };
+        let was_set = self.bits[i / 64] & (1 << (i % 64)) != 0;
+        self.bits[i / 64] &= !(1 << (i % 64));
+        was_set
+    }
+
+    pub fn covers(&self, p: (i64, i64)) -> bool {
+        self.index(p).is_some()
+    }
+
+    /// Returns a copy of the grid enlarged to also cover the box from `min` to
+    /// `max`.
+    pub fn grown(&self, min: (i64, i64), max: (i64, i64)) -> CaveGrid {
+        let own_max = self.max();
+        let mut grid = CaveGrid::new(
+            (min.0.min(self.min.0), min.1.min(self.min.1)),
+            (max.0.max(own_max.0), max.1.max(own_max.1)),
+        );
+        for p in self.cells().filter(|&p| self.contains(p)) {
+            grid.insert(p);
+        }
+        grid
+    }
+
+    /// Box around all occupied cells, `None` if there are none.
+    pub fn occupied_bounds(&self) -> Option<BoundingBox> {
+        self.cells()
+            .filter(|&p| self.contains(p))
+            .fold(None, |b: Option<BoundingBox>, p| {
+                Some(b.map_or(BoundingBox { min: p, max: p }, |b| b.extend(p)))
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|w| *w == 0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: (i64, i64),
+    pub max: (i64, i64),
+}
+
+impl BoundingBox {
+    fn extend(self, p: (i64, i64)) -> Self {
+        BoundingBox {
+            min: (self.min.0.min(p.0), self.min.1.min(p.1)),
+            max: (self.max.0.max(p.0), self.max.1.max(p.1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Air,
+    Rock,
+    Sand,
+    Water,
+    Source,
+}
+
+impl Cell {
+    /// How the puzzle text draws this kind of cell.
+    pub fn symbol(self) -> char {
+        match self {
+            Cell::Air => '.',
+            Cell::Rock => '#',
+            Cell::Sand => 'o',
+            Cell::Water => '~',
+            Cell::Source => '+',
+        }
+    }
+
+    fn color(self) -> [u8; 3] {
+        match self {
+            Cell::Air => [20, 20, 30],
+            Cell::Rock => [120, 120, 120],
+            Cell::Sand => [230, 190, 90],
+            Cell::Water => [60, 120, 230],
+            Cell::Source => [220, 40, 40],
+        }
+    }
+}
+
+/// Draws the cells inside `bounds` as text, one line per row.
+pub fn render_ascii(bounds: BoundingBox, cell: impl Fn((i64, i64)) -> Cell) -> String {
+    let mut s = String::new();
+    for y in bounds.min.1..=bounds.max.1 {
+        for x in bounds.min.0..=bounds.max.0 {
+            s.push(cell((x, y)).symbol());
+        }
+        s.push('\n');
+    }
+    s
+}
+
+/// Writes the cells inside `bounds` as a binary PPM image with `scale` pixels
+/// per cell side.
+pub fn write_ppm<W: Write>(
+    w: &mut W,
+    bounds: BoundingBox,
+    scale: usize,
+    cell: impl Fn((i64, i64)) -> Cell,
+) -> io::Result<()> {
+    let width = (bounds.max.0 - bounds.min.0 + 1) as usize;
+    let height = (bounds.max.1 - bounds.min.1 + 1) as usize;
+    write!(w, "P6\n{} {}\n255\n", width * scale, height * scale)?;
+    let mut row = Vec::with_capacity(width * scale * 3);
+    for y in bounds.min.1..=bounds.max.1 {
+        row.clear();
+        for x in bounds.min.0..=bounds.max.0 {
+            let color = cell((x, y)).color();
+            for _ in 0..scale {
+                row.extend_from_slice(&color);
+            }
+        }
+        for _ in 0..scale {
+            w.write_all(&row)?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationOutcome {
+    /// Grains that came to rest, including one that settles on a source.
+    pub grains: usize,
+    /// Whether a source got filled up with sand.
+    pub source_blocked: bool,
+    /// Whether a grain fell past the lowest rock, at any point so far.
+    pub fell_into_abyss: bool,
+    /// Box around all rock and settled sand, `None` if the cave is empty.
+    pub bounds: Option<BoundingBox>,
+    /// Total number of cells travelled by the settled grains on their way
+    /// down from a source. Falling again after a rock edit is not counted.
+    pub fall_steps: usize,
+}
+
+/// A cave being filled with sand (or water), poured one grain at a time.
+///
+/// The fall path of the previous grain from each source is kept on a stack,
+/// so a new grain starts from the last position that is still free instead of
+/// walking down from the source again. Iterating a `Cave` drops grains and
+/// yields where each one comes to rest.
+///
+/// Water takes its route from cells next to the path as well, so a drop of
+/// water always starts again from its source.
+///
+/// Rock can be added and removed between grains; sand that loses its support
+/// falls again. The floor stays where it was when the cave was created.
+#[derive(Debug, Clone)]
+pub struct Cave {
+    grid: CaveGrid,
+    settled: CaveGrid,
+    sources: Vec<(i64, i64)>,
+    floor: Option<i64>,
+    max_y: i64,
+    fall_directions: Vec<(i64, i64)>,
+    particle: Particle,
+    paths: Vec<Vec<(i64, i64)>>,
+    next_source: usize,
+    bounds: Option<BoundingBox>,
+    grains: usize,
+    fall_steps: usize,
+    source_blocked: bool,
+    fell_into_abyss: bool,
+}
+
+/// Saved state of a `Cave`, see `Cave::snapshot`.
+#[derive(Debug, Clone)]
+pub struct CaveSnapshot(Cave);
+
+impl Cave {
+    pub fn new(grid: CaveGrid, config: &CaveConfig) -> Self {
+        assert!(
+            config.fall_directions.iter().all(|d| d.1 > 0),
+            "Every fall direction must move down"
+        );
+        assert!(
+            config.particle == Particle::Sand || config.mode == SimulationMode::Abyss,
+            "Water needs SimulationMode::Abyss"
+        );
+        let max_y = lowest_row(grid.occupied_bounds(), &config.sources);
+        let floor = match config.mode {
+            SimulationMode::Floor { depth } => Some(max_y + depth as i64),
+            SimulationMode::Abyss => None,
+        };
+        let mut grid = grid;
+        if let Some(floor) = floor {
+            // Make room for every grain that can settle on the floor, the
+            // same way `CaveGrid::from_paths` sizes its grid.
+            let max_dx = config.max_dx();
+            for s in &config.sources {
+                let spread = max_dx * (floor - s.1);
+                let min = (s.0 - spread - max_dx, s.1);
+                let max = (s.0 + spread + max_dx, floor);
+                if !grid.covers(min) || !grid.covers(max) {
+                    grid = grid.grown(min, max);
+                }
+            }
+        }
+        let mut cave = Cave {
+            settled: CaveGrid::new(grid.min(), grid.max()),
+            grid,
+            sources: config.sources.clone(),
+            floor,
+            max_y,
+            fall_directions: config.fall_directions.clone(),
+            particle: config.particle,
+            paths: Vec::new(),
+            next_source: 0,
+            bounds: None,
+            grains: 0,
+            fall_steps: 0,
+            source_blocked: false,
+            fell_into_abyss: false,
+        };
+        cave.reset_paths();
+        cave
+    }
+
+    pub fn grid(&self) -> &CaveGrid {
+        &self.grid
+    }
+
+    pub fn cell(&self, p: (i64, i64)) -> Cell {
+        if self.settled.contains(p) {
+            match self.particle {
+                Particle::Sand => Cell::Sand,
+                Particle::Water => Cell::Water,
+            }
+        } else if self.grid.contains(p) {
+            Cell::Rock
+        } else if self.sources.contains(&p) {
+            Cell::Source
+        } else {
+            Cell::Air
+        }
+    }
+
+    /// Box around rock, settled sand and sources.
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        self.sources.iter().fold(self.bounds, |b, &p| {
+            Some(b.map_or(BoundingBox { min: p, max: p }, |b| b.extend(p)))
+        })
+    }
+
+    /// Draws the cave like the puzzle statement, cropped to `bounds()`.
+    pub fn render(&self) -> String {
+        self.bounds()
+            .map(|b| render_ascii(b, |p| self.cell(p)))
+            .unwrap_or_default()
+    }
+
+    pub fn write_ppm<W: Write>(&self, w: &mut W, scale: usize) -> io::Result<()> {
+        match self.bounds() {
+            Some(b) => write_ppm(w, b, scale, |p| self.cell(p)),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the simulation, writing a PPM frame to `dir` every `every` grains
+    /// and once more at the end. All frames cover the whole grid so they line
+    /// up. Returns the number of frames written.
+    pub fn dump_frames(&mut self, dir: &Path, every: usize, scale: usize) -> io::Result<usize> {
+        fs::create_dir_all(dir)?;
+        let bounds = BoundingBox {
+            min: self.grid.min(),
+            max: self.grid.max(),
+        };
+        let mut frames = 0;
+        let mut write_frame = |cave: &Cave| -> io::Result<()> {
+            let file = fs::File::create(dir.join(format!("frame_{frames:05}.ppm")))?;
+            write_ppm(&mut io::BufWriter::new(file), bounds, scale, |p| {
+                cave.cell(p)
+            })?;
+            frames += 1;
+            Ok(())
+        };
+        let mut grains = 0;
+        while self.drop_grain().is_some() {
+            grains += 1;
+            if grains % every.max(1) == 0 {
+                write_frame(self)?;
+            }
+        }
+        write_frame(self)?;
+        Ok(frames)
+    }
+
+    /// Drops grains until every source is blocked or drains into the abyss.
+    /// The outcome also covers grains dropped earlier.
+    pub fn drop_until_stable(&mut self) -> SimulationOutcome {
+        while self.drop_grain().is_some() {}
+        SimulationOutcome {
+            grains: self.grains,
+            source_blocked: self.source_blocked,
+            fell_into_abyss: self.fell_into_abyss,
+            bounds: self.bounds,
+            fall_steps: self.fall_steps,
+        }
+    }
+
+    /// Drops one grain from the next source that is still open, returning
+    /// where it came to rest. Returns `None` once no grain can settle.
+    pub fn drop_grain(&mut self) -> Option<(i64, i64)> {
+        for _ in 0..self.paths.len() {
+            let i = self.next_source;
+            self.next_source = (i + 1) % self.paths.len();
+            if let Some(p) = self.drop_from(i) {
+                return Some(p);
+            }
+        }
+        None
+    }
+
+    pub fn snapshot(&self) -> CaveSnapshot {
+        CaveSnapshot(self.clone())
+    }
+
+    pub fn restore(&mut self, snapshot: &CaveSnapshot) {
+        self.clone_from(&snapshot.0);
+    }
+
+    /// Adds a straight rock segment from `from` to `to`, replacing any sand in
+    /// the way.
+    pub fn add_rock_segment(&mut self, from: (i64, i64), to: (i64, i64)) -> Result<(), String> {
+        let cells = segment_cells(from, to)?;
+        let max_dx = self
+            .fall_directions
+            .iter()
+            .map(|d| d.0.abs())
+            .max()
+            .unwrap_or(0);
+        let min = (from.0.min(to.0) - max_dx, from.1.min(to.1));
+        let max = (from.0.max(to.0) + max_dx, from.1.max(to.1));
+        if !self.grid.covers(min) || !self.grid.covers(max) {
+            self.grid = self.grid.grown(min, max);
+            self.settled = self.settled.grown(min, max);
+        }
+        for p in cells {
+            self.grid.insert(p);
+            if self.settled.remove(p) {
+                self.grains -= 1;
+            }
+        }
+        if self.floor.is_none() {
+            self.max_y = self.max_y.max(max.1);
+        }
+        self.reset_paths();
+        Ok(())
+    }
+
+    /// Removes the rock on the segment from `from` to `to` and lets sand that
+    /// was resting on it fall again.
+    pub fn remove_rock_segment(&mut self, from: (i64, i64), to: (i64, i64)) -> Result<(), String> {
+        for p in segment_cells(from, to)? {
+            if !self.settled.contains(p) {
+                self.grid.remove(p);
+            }
+        }
+        if self.floor.is_none() {
+            self.max_y = lowest_row(self.rock_bounds(), &self.sources);
+        }
+        self.resettle();
+        self.reset_paths();
+        Ok(())
+    }
+
+    fn reset_paths(&mut self) {
+        self.paths = self
+            .sources
+            .iter()
+            .map(|&source| {
+                if self.grid.contains(source) {
+                    Vec::new()
+                } else {
+                    vec![source]
+                }
+            })
+            .collect();
+        self.source_blocked = self.paths.iter().any(|p| p.is_empty());
+        self.bounds = self.grid.occupied_bounds();
+    }
+
+    /// Box around the rock alone, without settled sand.
+    fn rock_bounds(&self) -> Option<BoundingBox> {
+        self.grid
+            .cells()
+            .filter(|&p| self.grid.contains(p) && !self.settled.contains(p))
+            .fold(None, |b: Option<BoundingBox>, p| {
+                Some(b.map_or(BoundingBox { min: p, max: p }, |b| b.extend(p)))
+            })
+    }
+
+    fn is_free(&self, p: (i64, i64)) -> bool {
+        self.floor.is_none_or(|floor| p.1 < floor) && !self.grid.contains(p)
+    }
+
+    /// Lets sand that lost its support fall again, lowest grains first so
+    /// that everything below a grain has settled before it moves.
+    fn resettle(&mut self) {
+        let grains = self
+            .settled
+            .cells()
+            .filter(|&p| self.settled.contains(p))
+            .collect::<Vec<_>>();
+        for &p in grains.iter().rev() {
+            if self.next_move(p).is_some() {
+                self.grid.remove(p);
+                self.settled.remove(p);
+                self.grains -= 1;
+                self.fall(&mut vec![p]);
+            }
+        }
+    }
+
+    /// Drops one grain from source `i`, or returns `None` if it is blocked or
+    /// the grain falls into the abyss.
+    fn drop_from(&mut self, i: usize) -> Option<(i64, i64)> {
+        let mut path = std::mem::take(&mut self.paths[i]);
+        // Grains from other sources may have landed on this source's path.
+        if self.paths.len() > 1
+            && let Some(blocked) = path.iter().position(|&p| self.grid.contains(p))
+        {
+            path.truncate(blocked);
+        }
+        let p = self.fall(&mut path);
+        if p.is_some() {
+            // The path still runs from the source to just above the grain.
+            self.fall_steps += path.len();
+        }
+        self.source_blocked |= p.is_some() && path.is_empty();
+        if self.particle == Particle::Water && !path.is_empty() {
+            path.truncate(1);
+        }
+        self.paths[i] = path;
+        p
+    }
+
+    /// Where a grain at `p` moves next, `None` if it comes to rest there.
+    fn next_move(&self, p: (i64, i64)) -> Option<(i64, i64)> {
+        match self.particle {
+            Particle::Sand => self
+                .fall_directions
+                .iter()
+                .map(|d| (p.0 + d.0, p.1 + d.1))
+                .find(|&q| self.is_free(q)),
+            Particle::Water => {
+                let below = (p.0, p.1 + 1);
+                if self.is_free(below) {
+                    return Some(below);
+                }
+                // Distance to the nearest cell in direction `dx` that water
+                // can fall from, if no wall is in the way.
+                let edge = |dx: i64| {
+                    let mut q = p;
+                    loop {
+                        q.0 += dx;
+                        if !self.is_free(q) {
+                            return None;
+                        }
+                        if self.is_free((q.0, q.1 + 1)) {
+                            return Some((q.0 - p.0).abs());
+                        }
+                    }
+                };
+                match (edge(-1), edge(1)) {
+                    (Some(l), Some(r)) if r < l => Some((p.0 + 1, p.1)),
+                    (Some(_), _) => Some((p.0 - 1, p.1)),
+                    (None, Some(_)) => Some((p.0 + 1, p.1)),
+                    (None, None) => None,
+                }
+            }
+        }
+    }
+
+    /// Moves the grain at the end of `path` until it settles, leaving the
+    /// path to its resting place on the stack.
+    fn fall(&mut self, path: &mut Vec<(i64, i64)>) -> Option<(i64, i64)> {
+        loop {
+            let p = *path.last()?;
+            if self.floor.is_none() && p.1 > self.max_y {
+                path.clear();
+                self.fell_into_abyss = true;
+                return None;
+            }
+            if let Some(q) = self.next_move(p) {
+                path.push(q);
+            } else {
+                self.grid.insert(p);
+                self.settled.insert(p);
+                self.grains += 1;
+                path.pop();
+                self.bounds = Some(
+                    self.bounds
+                        .map_or(BoundingBox { min: p, max: p }, |b| b.extend(p)),
+                );
+                return Some(p);
+            }
+        }
+    }
+}
+
+impl Iterator for Cave {
+    type Item = (i64, i64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.drop_grain()
+    }
+}
+
+fn segment_cells(from: (i64, i64), to: (i64, i64)) -> Result<Vec<(i64, i64)>, String> {
+    if from.0 != to.0 && from.1 != to.1 {
+        return Err(format!(
+            "Diagonal segment from {},{} to {},{}",
+            from.0, from.1, to.0, to.1
+        ));
+    }
+    let mut p = from;
+    let mut cells = vec![p];
+    while p != to {
+        p = (p.0 + (to.0 - p.0).signum(), p.1 + (to.1 - p.1).signum());
+        cells.push(p);
+    }
+    Ok(cells)
+}
+
+/// Lowest row that holds rock or a source; floors are measured from here.
+fn lowest_row(bounds: Option<BoundingBox>, sources: &[(i64, i64)]) -> i64 {
+    sources
+        .iter()
+        .map(|s| s.1)
+        .chain(bounds.map(|b| b.max.1))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Counts the grains that settle in floor mode without simulating them.
+///
+/// With a floor the pile only stops growing once every cell a grain could
+/// reach is filled, so the answer is the number of free cells reachable from
+/// a source. Those are found with one sweep over the rows below the sources.
+pub fn count_with_floor_fast(grid: &CaveGrid, config: &CaveConfig) -> usize {
+    let SimulationMode::Floor { depth } = config.mode else {
+        panic!("count_with_floor_fast needs a floor");
+    };
+    assert_eq!(Particle::Sand, config.particle, "Only sand piles up");
+    let Some(top) = config.sources.iter().map(|s| s.1).min() else {
+        return 0;
+    };
+    let floor = lowest_row(grid.occupied_bounds(), &config.sources) + depth as i64;
+    let max_dx = config.max_dx();
+    let spread = |s: &(i64, i64)| max_dx * (floor - s.1);
+    let min_x = config
+        .sources
+        .iter()
+        .map(|s| s.0 - spread(s))
+        .min()
+        .unwrap();
+    let max_x = config
+        .sources
+        .iter()
+        .map(|s| s.0 + spread(s))
+        .max()
+        .unwrap();
+    if floor <= top {
+        return 0;
+    }
+    let mut reached = CaveGrid::new((min_x, top), (max_x, floor - 1));
+    let mut count = 0;
+    for p in reached.cells() {
+        if grid.contains(p) {
+            continue;
+        }
+        if config.sources.contains(&p)
+            || config
+                .fall_directions
+                .iter()
+                .any(|d| reached.contains((p.0 - d.0, p.1 - d.1)))
+        {
+            reached.insert(p);
+            count += 1;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EXAMPLE: &str = "498,4 -> 498,6 -> 496,6\n503,4 -> 502,4 -> 502,9 -> 494,9";
+
+    fn example_paths() -> Vec<Vec<(i64, i64)>> {
+        parse_paths(EXAMPLE).unwrap()
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        assert_eq!(
+            Ok(vec![vec![(498, 4), (498, 6), (496, 6)], vec![(-1, 0)]]),
+            parse_paths("498,4 -> 498,6 -> 496,6\n\n-1,0\n")
+        );
+        assert_eq!(
+            Err("Line 2: malformed point \" 4x5 \"".to_string()),
+            parse_paths("1,1 -> 1,2\n3,3 -> 4x5 -> 4,4")
+        );
+        assert_eq!(
+            Err("Line 1: malformed point \" 1,\"".to_string()),
+            parse_paths("0,1 -> 1,")
+        );
+        assert_eq!(
+            Err("Line 3: diagonal segment from 2,2 to 3,3".to_string()),
+            parse_paths("0,0\n1,1 -> 1,2\n2,0 -> 2,2 -> 3,3")
+        );
+        assert!(parse_paths("1,1 ->").is_err());
+        assert!(parse_paths("1,1,1 -> 1,2").is_err());
+    }
+
+    #[test]
+    fn test_single_point_path() {
+        let config = CaveConfig {
+            sources: vec![(5, 0)],
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&parse_paths("5,3").unwrap(), &config);
+        assert_eq!(1, grid.len());
+        // The first grain lands on the rock and slides off into the abyss.
+        let outcome = Cave::new(grid, &config).drop_until_stable();
+        assert_eq!(0, outcome.grains);
+        assert!(outcome.fell_into_abyss);
+    }
+
+    #[test]
+    fn test_grid_bounds() {
+        let paths = vec![vec![(498, 4), (498, 6), (496, 6)]];
+        let config = CaveConfig {
+            mode: SimulationMode::Floor { depth: 2 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        assert_eq!((491, 0), grid.min());
+        assert_eq!((509, 8), grid.max());
+        assert_eq!(5, grid.len());
+        assert!(grid.contains((497, 6)));
+        assert!(!grid.contains((497, 5)));
+        assert!(!grid.contains((0, 0)));
+        assert!(!grid.contains((1000, 1000)));
+    }
+
+    #[test]
+    fn test_grid_insert() {
+        let mut grid = CaveGrid::new((10, 0), (20, 4));
+        assert!(grid.is_empty());
+        assert!(grid.insert((10, 0)));
+        assert!(grid.insert((20, 4)));
+        assert!(!grid.insert((20, 4)));
+        assert_eq!(2, grid.len());
+        assert!(grid.contains((20, 4)));
+        assert!(!grid.contains((-1, 0)));
+        assert!(!grid.contains((10, -1)));
+    }
+
+    #[test]
+    fn test_simulator_resumes_path() {
+        let paths = example_paths();
+        let config = CaveConfig::default();
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let grains = Cave::new(grid, &config).collect::<Vec<_>>();
+        assert_eq!(24, grains.len());
+        assert_eq!((500, 8), grains[0]);
+        assert_eq!((499, 8), grains[1]);
+        assert_eq!((501, 8), grains[2]);
+        assert_eq!((495, 8), grains[23]);
+    }
+
+    #[test]
+    fn test_simulator_blocked_source() {
+        let config = CaveConfig {
+            sources: vec![(5, 0)],
+            mode: SimulationMode::Floor { depth: 2 },
+            ..Default::default()
+        };
+        let mut grid = CaveGrid::new((0, 0), (10, 4));
+        grid.insert((5, 0));
+        assert_eq!(0, Cave::new(grid, &config).count());
+
+        let grid = CaveGrid::new((0, 0), (10, 2));
+        let grains = Cave::new(grid, &config).collect::<Vec<_>>();
+        assert_eq!(4, grains.len());
+        assert_eq!(Some(&(5, 0)), grains.last());
+    }
+
+    #[test]
+    fn test_simulator_config() {
+        let paths = vec![vec![(10, 6), (20, 6)]];
+
+        // Two sources share the floor and their piles merge.
+        let config = CaveConfig {
+            sources: vec![(12, 0), (18, 0)],
+            mode: SimulationMode::Floor { depth: 1 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let grains = Cave::new(grid, &config).collect::<Vec<_>>();
+        assert!(grains.contains(&(12, 0)));
+        assert!(grains.contains(&(18, 0)));
+
+        // Without down-left the pile only grows to the right.
+        let config = CaveConfig {
+            sources: vec![(15, 4)],
+            fall_directions: vec![(0, 1), (1, 1)],
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let grains = Cave::new(grid, &config).collect::<Vec<_>>();
+        assert_eq!(vec![(15, 5), (16, 5), (15, 4)], grains);
+
+        // A floor far below the rock.
+        let config = CaveConfig {
+            sources: vec![(15, 0)],
+            mode: SimulationMode::Floor { depth: 4 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&[], &config);
+        assert_eq!(16, Cave::new(grid, &config).count());
+    }
+
+    #[test]
+    fn test_simulation_outcome() {
+        let paths = example_paths();
+        let config = CaveConfig::default();
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let outcome = Cave::new(grid, &config).drop_until_stable();
+        assert_eq!(24, outcome.grains);
+        assert!(outcome.fell_into_abyss);
+        assert!(!outcome.source_blocked);
+        assert_eq!(
+            Some(BoundingBox {
+                min: (494, 2),
+                max: (503, 9)
+            }),
+            outcome.bounds
+        );
+
+        let config = CaveConfig {
+            mode: SimulationMode::Floor { depth: 2 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let outcome = Cave::new(grid, &config).drop_until_stable();
+        assert_eq!(93, outcome.grains);
+        assert!(!outcome.fell_into_abyss);
+        assert!(outcome.source_blocked);
+        assert_eq!(
+            Some(BoundingBox {
+                min: (490, 0),
+                max: (510, 10)
+            }),
+            outcome.bounds
+        );
+
+        // A single grain on a floor right below the source travels one cell.
+        let config = CaveConfig {
+            sources: vec![(1, 0)],
+            mode: SimulationMode::Floor { depth: 2 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&[], &config);
+        let outcome = Cave::new(grid, &config).drop_until_stable();
+        assert_eq!(4, outcome.grains);
+        assert_eq!(3, outcome.fall_steps);
+    }
+
+    #[test]
+    fn test_render() {
+        let paths = example_paths();
+        let config = CaveConfig::default();
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let mut cave = Cave::new(grid, &config);
+        assert_eq!(
+            "......+...
+..........
+..........
+..........
+....#...##
+....#...#.
+..###...#.
+........#.
+........#.
+#########.
+",
+            cave.render()
+        );
+        cave.drop_until_stable();
+        assert_eq!(
+            "......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+...o#ooo#.
+..###ooo#.
+....oooo#.
+.o.ooooo#.
+#########.
+",
+            cave.render()
+        );
+
+        let mut ppm = Vec::new();
+        cave.write_ppm(&mut ppm, 2).unwrap();
+        assert!(ppm.starts_with(b"P6\n20 20\n255\n"));
+        assert_eq!(13 + 20 * 20 * 3, ppm.len());
+    }
+
+    #[test]
+    fn test_count_with_floor_fast() {
+        let paths = example_paths();
+        let config = CaveConfig {
+            mode: SimulationMode::Floor { depth: 2 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        assert_eq!(93, count_with_floor_fast(&grid, &config));
+
+        let config = CaveConfig {
+            sources: vec![(498, 5)],
+            ..config
+        };
+        assert_eq!(0, count_with_floor_fast(&grid, &config));
+    }
+
+    #[test]
+    fn test_floor_stops_long_falls() {
+        // A grain moving two rows at a time would step over the floor.
+        let config = CaveConfig {
+            mode: SimulationMode::Floor { depth: 1 },
+            fall_directions: vec![(0, 2)],
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&[], &config);
+        assert_eq!(1, count_with_floor_fast(&grid, &config));
+        let outcome = Cave::new(grid, &config).drop_until_stable();
+        assert_eq!(1, outcome.grains);
+        assert!(outcome.source_blocked);
+    }
+
+    #[test]
+    fn test_floor_grows_small_grid() {
+        // The grid only holds the source; the pile on the floor needs more.
+        let config = CaveConfig {
+            sources: vec![(5, 0)],
+            mode: SimulationMode::Floor { depth: 3 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::new((5, 0), (5, 0));
+        assert_eq!(9, count_with_floor_fast(&grid, &config));
+        let outcome = Cave::new(grid, &config).drop_until_stable();
+        assert_eq!(9, outcome.grains);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_fast_count_matches_simulation(
+            segments in prop::collection::vec((0i64..30, 1i64..15, 0i64..6, any::<bool>()), 0..8),
+            sources in prop::collection::vec((0i64..30, 0i64..3), 1..3),
+            depth in 1usize..4,
+            only_right in any::<bool>(),
+        ) {
+            let paths = segments
+                .iter()
+                .map(|&(x, y, len, vertical)| {
+                    if vertical {
+                        vec![(x, y), (x, y + len)]
+                    } else {
+                        vec![(x, y), (x + len, y)]
+                    }
+                })
+                .collect::<Vec<_>>();
+            let fall_directions = if only_right {
+                vec![(0, 1), (1, 1), (2, 1)]
+            } else {
+                DEFAULT_FALL_DIRECTIONS.to_vec()
+            };
+            let config = CaveConfig {
+                sources,
+                mode: SimulationMode::Floor { depth },
+                fall_directions,
+                particle: Particle::Sand,
+            };
+            let grid = CaveGrid::from_paths(&paths, &config);
+            let expected = Cave::new(grid.clone(), &config).drop_until_stable().grains;
+            prop_assert_eq!(expected, count_with_floor_fast(&grid, &config));
+        }
+    }
+
+    #[test]
+    fn test_cave_editing() {
+        let config = CaveConfig::default();
+        let mut cave = Cave::new(CaveGrid::from_paths(&example_paths(), &config), &config);
+        assert_eq!(Some((500, 8)), cave.drop_grain());
+        assert_eq!(24, cave.drop_until_stable().grains);
+        let before = cave.render();
+        let snapshot = cave.snapshot();
+
+        // Without the ledge the sand on it slides down and more fits in.
+        cave.remove_rock_segment((498, 4), (498, 6)).unwrap();
+        assert_eq!(
+            "......+...
+..........
+..........
+......o...
+.....ooo##
+....oooo#.
+..##oooo#.
+...ooooo#.
+.ooooooo#.
+#########.
+",
+            cave.render()
+        );
+        assert_eq!(29, cave.drop_until_stable().grains);
+
+        // Rock outside the grid grows it, rock on sand replaces the grain.
+        cave.add_rock_segment((505, 3), (512, 3)).unwrap();
+        cave.add_rock_segment((496, 7), (496, 7)).unwrap();
+        let outcome = cave.drop_until_stable();
+        assert_eq!(29, outcome.grains);
+        assert_eq!((512, 9), outcome.bounds.unwrap().max);
+        assert_eq!(Cell::Rock, cave.cell((512, 3)));
+        assert_eq!(Cell::Rock, cave.cell((496, 7)));
+
+        assert!(cave.add_rock_segment((0, 0), (1, 1)).is_err());
+        assert!(cave.remove_rock_segment((0, 0), (1, 1)).is_err());
+
+        cave.restore(&snapshot);
+        assert_eq!(before, cave.render());
+        assert_eq!(24, cave.drop_until_stable().grains);
+    }
+
+    #[test]
+    fn test_remove_floor_support() {
+        // Sand resting on a shelf above the floor drops to the floor.
+        let config = CaveConfig {
+            sources: vec![(5, 0)],
+            mode: SimulationMode::Floor { depth: 3 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&[vec![(3, 2), (7, 2)]], &config);
+        let mut cave = Cave::new(grid, &config);
+        assert_eq!(Some((5, 1)), cave.drop_grain());
+        cave.remove_rock_segment((3, 2), (7, 2)).unwrap();
+        assert_eq!(Cell::Air, cave.cell((5, 1)));
+        assert_eq!(Cell::Sand, cave.cell((5, 4)));
+        assert_eq!(Some((4, 4)), cave.drop_grain());
+
+        // The grain that fell again only counts the step it took at first,
+        // three fewer than one dropped onto the floor directly.
+        let outcome = cave.drop_until_stable();
+        let config = CaveConfig {
+            mode: SimulationMode::Floor { depth: 5 },
+            ..config
+        };
+        let direct = Cave::new(CaveGrid::from_paths(&[], &config), &config).drop_until_stable();
+        assert_eq!(direct.grains, outcome.grains);
+        assert_eq!(direct.fall_steps - 3, outcome.fall_steps);
+    }
+
+    #[test]
+    fn test_edits_keep_abyss_history() {
+        let paths = example_paths();
+        let config = CaveConfig::default();
+        let mut cave = Cave::new(CaveGrid::from_paths(&paths, &config), &config);
+        assert!(cave.drop_until_stable().fell_into_abyss);
+        // Blocking the source means no grain falls any more.
+        cave.add_rock_segment((500, 0), (500, 0)).unwrap();
+        let outcome = cave.drop_until_stable();
+        assert!(outcome.source_blocked);
+        assert!(outcome.fell_into_abyss);
+
+        // Without the bottom row the foot of the right wall is the lowest
+        // rock, so grains count as lost as soon as they pass row 8.
+        let mut cave = Cave::new(CaveGrid::from_paths(&paths, &config), &config);
+        cave.remove_rock_segment((502, 9), (494, 9)).unwrap();
+        assert_eq!(8, cave.max_y);
+    }
+
+    #[test]
+    fn test_water_fills_basin() {
+        let paths = parse_paths("495,2 -> 495,5 -> 505,5 -> 505,2\n499,4 -> 500,4").unwrap();
+        let config = CaveConfig {
+            particle: Particle::Water,
+            ..Default::default()
+        };
+        let mut cave = Cave::new(CaveGrid::from_paths(&paths, &config), &config);
+        let outcome = cave.drop_until_stable();
+        assert_eq!(25, outcome.grains);
+        assert!(outcome.fell_into_abyss);
+        assert_eq!(
+            ".....+.....
+...........
+#~~~~~~~~~#
+#~~~~~~~~~#
+#~~~##~~~~#
+###########
+",
+            cave.render()
+        );
+
+        // Opening the right wall down to the bottom row lets everything
+        // above it drain away, and new water flows straight out.
+        cave.remove_rock_segment((505, 2), (505, 3)).unwrap();
+        assert_eq!(
+            ".....+.....
+...........
+#..........
+#..........
+#~~~##~~~~#
+###########
+",
+            cave.render()
+        );
+        assert_eq!(7, cave.drop_until_stable().grains);
+    }
+
+    #[test]
+    fn test_water_flows_off_ledge() {
+        // Water lands on a ledge, runs off its nearer right end and then
+        // spreads out on the floor of the basin below.
+        let paths = parse_paths("497,2 -> 501,2\n495,4 -> 495,6 -> 505,6 -> 505,4").unwrap();
+        let config = CaveConfig {
+            particle: Particle::Water,
+            ..Default::default()
+        };
+        let mut cave = Cave::new(CaveGrid::from_paths(&paths, &config), &config);
+        assert_eq!(Some((502, 5)), cave.drop_grain());
+        assert_eq!(Some((501, 5)), cave.drop_grain());
+    }
+
+    #[test]
+    #[should_panic(expected = "Water needs SimulationMode::Abyss")]
+    fn test_water_needs_abyss() {
+        let config = CaveConfig {
+            particle: Particle::Water,
+            mode: SimulationMode::Floor { depth: 2 },
+            ..Default::default()
+        };
+        Cave::new(CaveGrid::from_paths(&[], &config), &config);
+    }
+
+    #[test]
+    fn test_left_edge() {
+        // Rock at x = 0 with sand sliding off its left end right away.
+        let paths = vec![vec![(0, 3), (2, 3)]];
+        let config = CaveConfig {
+            sources: vec![(0, 0)],
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let outcome = Cave::new(grid, &config).drop_until_stable();
+        assert_eq!(0, outcome.grains);
+        assert!(outcome.fell_into_abyss);
+
+        // A source at x = 0 over a floor spreads sand to negative x.
+        let config = CaveConfig {
+            sources: vec![(0, 0)],
+            mode: SimulationMode::Floor { depth: 2 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        let grains = Cave::new(grid, &config).collect::<Vec<_>>();
+        assert!(grains.contains(&(-4, 4)));
+        assert_eq!(Some(&(0, 0)), grains.last());
+        assert_eq!(21, grains.len());
+
+        // A source below the lowest rock still gets a floor under it.
+        let config = CaveConfig {
+            sources: vec![(0, 10)],
+            mode: SimulationMode::Floor { depth: 1 },
+            ..Default::default()
+        };
+        let grid = CaveGrid::from_paths(&paths, &config);
+        assert_eq!(1, Cave::new(grid, &config).count());
+    }
+}
@@ -1,2 +1,6 @@
+pub mod cave;
+pub mod packet;
 pub mod parse_utils;
+pub mod sensor;
+pub mod sensor3d;
 pub mod sorted_set;
@@ -0,0 +1,541 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    io::{self, BufRead},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Value(u8),
+    List(Vec<Packet>),
+}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if *self == *other {
+            return Some(Ordering::Equal);
+        }
+        if let Some(b) = compare_packets(self, other) {
+            if b {
+                Some(Ordering::Less)
+            } else {
+                Some(Ordering::Greater)
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Packet::Value(n) => write!(f, "{n}"),
+            Packet::List(l) => {
+                write!(f, "[")?;
+                for (i, p) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{p}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<&Packet> for serde_json::Value {
+    fn from(p: &Packet) -> Self {
+        match p {
+            Packet::Value(n) => serde_json::Value::from(*n),
+            Packet::List(l) => serde_json::Value::Array(l.iter().map(Into::into).collect()),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<&serde_json::Value> for Packet {
+    type Error = String;
+
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            serde_json::Value::Number(n) => n
+                .as_u64()
+                .and_then(|n| u8::try_from(n).ok())
+                .map(Packet::Value)
+                .ok_or_else(|| format!("{n} is not a valid packet integer")),
+            serde_json::Value::Array(a) => a
+                .iter()
+                .map(Packet::try_from)
+                .collect::<Result<Vec<_>, _>>()
+                .map(Packet::List),
+            other => Err(format!("{other} is not a packet list or integer")),
+        }
+    }
+}
+
+/// Packets nested deeper than this are rejected by the parser.
+pub const MAX_DEPTH: usize = 256;
+
+pub fn parse_packet(s: &[char]) -> (Packet, &[char]) {
+    parse_nested(s, 0).unwrap_or_else(|e| panic!("{e}"))
+}
+
+pub fn parse_packet_line(line: &str) -> Packet {
+    try_parse_packet(line).unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Parses a whole line as one packet, reporting malformed input as an error
+/// instead of panicking.
+pub fn try_parse_packet(line: &str) -> Result<Packet, String> {
+    let chars = line.chars().collect::<Vec<_>>();
+    match parse_nested(&chars, 0)? {
+        (p, []) => Ok(p),
+        (_, rest) => Err(format!(
+            "Unexpected trailing input {:?}",
+            rest.iter().collect::<String>()
+        )),
+    }
+}
+
+fn parse_nested(s: &[char], depth: usize) -> Result<(Packet, &[char]), String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("Packet is nested deeper than {MAX_DEPTH} levels"));
+    }
+    match s {
+        [] => Err("Unexpected end of packet".to_string()),
+        ['[', ']', rest @ ..] => Ok((Packet::List(Vec::new()), rest)),
+        ['[', rest @ ..] => {
+            let mut v = Vec::new();
+            let mut s = rest;
+            loop {
+                let (p, rest) = parse_nested(s, depth + 1)?;
+                v.push(p);
+                match rest {
+                    [',', rest @ ..] => s = rest,
+                    [']', rest @ ..] => return Ok((Packet::List(v), rest)),
+                    [] => return Err("Unterminated list".to_string()),
+                    [c, ..] => return Err(format!("Unexpected character {c:?} in list")),
+                }
+            }
+        }
+        [c, ..] => {
+            let consumed = s.iter().take_while(|c| c.is_ascii_digit()).count();
+            if consumed == 0 {
+                return Err(format!("Unexpected character {c:?}"));
+            }
+            let number_chars: String = s[..consumed].iter().collect();
+            let n = number_chars
+                .parse::<u8>()
+                .map_err(|_| format!("{number_chars} is not a valid packet integer"))?;
+            Ok((Packet::Value(n), &s[consumed..]))
+        }
+    }
+}
+
+pub fn compare_packets(p1: &Packet, p2: &Packet) -> Option<bool> {
+    match (p1, p2) {
+        (Packet::Value(n1), Packet::Value(n2)) => {
+            if n1 == n2 {
+                None
+            } else {
+                Some(n1 < n2)
+            }
+        }
+        (Packet::List(l1), Packet::List(l2)) => {
+            for (p1, p2) in l1.iter().zip(l2.iter()) {
+                if let Some(r) = compare_packets(p1, p2) {
+                    return Some(r);
+                }
+            }
+            if l1.len() == l2.len() {
+                None
+            } else {
+                Some(l1.len() < l2.len())
+            }
+        }
+        (&Packet::Value(v1), &Packet::List(_)) => {
+            compare_packets(&Packet::List(vec![Packet::Value(v1)]), p2)
+        }
+        (&Packet::List(_), &Packet::Value(v2)) => {
+            compare_packets(p1, &Packet::List(vec![Packet::Value(v2)]))
+        }
+    }
+}
+
+/// Returns the 1-based position each key would have if `packets` and `keys`
+/// were sorted together, without sorting anything.
+pub fn rank_of(packets: &[Packet], keys: &[Packet]) -> Vec<usize> {
+    let mut ranks = key_ranks(keys);
+    for p in packets {
+        count_less(&mut ranks, p, keys);
+    }
+    ranks
+}
+
+/// Like `rank_of`, but reads one packet per non-empty line so that memory use
+/// does not grow with the input.
+pub fn rank_of_stream<R: BufRead>(reader: R, keys: &[Packet]) -> io::Result<Vec<usize>> {
+    let mut ranks = key_ranks(keys);
+    for line in reader.lines() {
+        let line = line?;
+        if !line.is_empty() {
+            let p = try_parse_packet(&line).map_err(invalid_data)?;
+            count_less(&mut ranks, &p, keys);
+        }
+    }
+    Ok(ranks)
+}
+
+/// Sums the 1-based indices of the pairs that are in the right order, reading
+/// the pairs one line at a time.
+pub fn sum_ordered_pairs<R: BufRead>(reader: R) -> io::Result<usize> {
+    let mut lines = reader
+        .lines()
+        .filter(|l| !matches!(l, Ok(l) if l.is_empty()));
+    let mut index_sum = 0;
+    let mut index = 0;
+    while let Some(first) = lines.next() {
+        index += 1;
+        let Some(second) = lines.next() else {
+            return Err(invalid_data(format!("Pair {index} has only one packet")));
+        };
+        let p1 = try_parse_packet(&first?).map_err(invalid_data)?;
+        let p2 = try_parse_packet(&second?).map_err(invalid_data)?;
+        match compare_packets(&p1, &p2) {
+            Some(true) => index_sum += index,
+            Some(false) => {}
+            None => {
+                return Err(invalid_data(format!(
+                    "Cannot determine order of pair {index}"
+                )));
+            }
+        }
+    }
+    Ok(index_sum)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn key_ranks(keys: &[Packet]) -> Vec<usize> {
+    let mut ranks = vec![1; keys.len()];
+    for k in keys {
+        count_less(&mut ranks, k, keys);
+    }
+    ranks
+}
+
+fn count_less(ranks: &mut [usize], p: &Packet, keys: &[Packet]) {
+    for (rank, key) in ranks.iter_mut().zip(keys.iter()) {
+        if compare_packets(p, key) == Some(true) {
+            *rank += 1;
+        }
+    }
+}
+
+/// The comparison rule that decided the order of two packets.
+///
+/// Promoting an integer to a list never decides the order by itself, so it
+/// has no variant here; see `OrderExplanation::promoted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderRule {
+    /// Two integers at the same position differed.
+    IntegerCompare,
+    /// One list ran out of items before the other.
+    ListExhaustion,
+}
+
+/// Why two packets are (or are not) in the right order.
+///
+/// `rule` names the final comparison that decided it. If an integer was
+/// promoted to a list on the way there, that is reported through `promoted`
+/// alongside the rule rather than as a rule of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderExplanation {
+    /// Same result as `compare_packets`.
+    pub in_order: Option<bool>,
+    /// List indices leading to the deciding element. For list exhaustion the
+    /// last index is the first position missing from the shorter list.
+    pub path: Vec<usize>,
+    /// The deciding rule, `None` if the packets are equal.
+    pub rule: Option<OrderRule>,
+    /// Whether an integer had to be promoted to a list along the path.
+    pub promoted: bool,
+    /// Step-by-step walkthrough in the format of the puzzle statement.
+    pub trace: String,
+}
+
+/// Compares two packets like `compare_packets`, but also records why they are
+/// (or are not) in the right order.
+pub fn explain_order(p1: &Packet, p2: &Packet) -> OrderExplanation {
+    let mut explanation = OrderExplanation {
+        in_order: None,
+        path: Vec::new(),
+        rule: None,
+        promoted: false,
+        trace: String::new(),
+    };
+    explanation.in_order = explain(p1, p2, 0, &mut explanation);
+    explanation
+}
+
+fn explain(p1: &Packet, p2: &Packet, depth: usize, ex: &mut OrderExplanation) -> Option<bool> {
+    trace_line(ex, depth, &format!("Compare {p1} vs {p2}"));
+    match (p1, p2) {
+        (Packet::Value(n1), Packet::Value(n2)) => {
+            if n1 == n2 {
+                return None;
+            }
+            ex.rule = Some(OrderRule::IntegerCompare);
+            let r = n1 < n2;
+            if r {
+                trace_line(
+                    ex,
+                    depth + 1,
+                    "Left side is smaller, so inputs are in the right order",
+                );
+            } else {
+                trace_line(
+                    ex,
+                    depth + 1,
+                    "Right side is smaller, so inputs are not in the right order",
+                );
+            }
+            Some(r)
+        }
+        (Packet::List(l1), Packet::List(l2)) => {
+            for (i, (q1, q2)) in l1.iter().zip(l2.iter()).enumerate() {
+                ex.path.push(i);
+                if let Some(r) = explain(q1, q2, depth + 1, ex) {
+                    return Some(r);
+                }
+                ex.path.pop();
+            }
+            if l1.len() == l2.len() {
+                return None;
+            }
+            ex.path.push(l1.len().min(l2.len()));
+            ex.rule = Some(OrderRule::ListExhaustion);
+            let r = l1.len() < l2.len();
+            if r {
+                trace_line(
+                    ex,
+                    depth + 1,
+                    "Left side ran out of items, so inputs are in the right order",
+                );
+            } else {
+                trace_line(
+                    ex,
+                    depth + 1,
+                    "Right side ran out of items, so inputs are not in the right order",
+                );
+            }
+            Some(r)
+        }
+        (&Packet::Value(v1), &Packet::List(_)) => {
+            let promoted = Packet::List(vec![Packet::Value(v1)]);
+            trace_line(
+                ex,
+                depth + 1,
+                &format!("Mixed types; convert left to {promoted} and retry comparison"),
+            );
+            let r = explain(&promoted, p2, depth + 1, ex);
+            ex.promoted |= r.is_some();
+            r
+        }
+        (&Packet::List(_), &Packet::Value(v2)) => {
+            let promoted = Packet::List(vec![Packet::Value(v2)]);
+            trace_line(
+                ex,
+                depth + 1,
+                &format!("Mixed types; convert right to {promoted} and retry comparison"),
+            );
+            let r = explain(p1, &promoted, depth + 1, ex);
+            ex.promoted |= r.is_some();
+            r
+        }
+    }
+}
+
+fn trace_line(ex: &mut OrderExplanation, depth: usize, text: &str) {
+    ex.trace.push_str(&"  ".repeat(depth));
+    ex.trace.push_str("- ");
+    ex.trace.push_str(text);
+    ex.trace.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn packet(s: &str) -> Packet {
+        parse_packet(&s.chars().collect::<Vec<_>>()).0
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let s = "[1,[2,[3,[4,[5,6,7]]]],8,9]";
+        assert_eq!(s, packet(s).to_string());
+        assert_eq!("[]", packet("[]").to_string());
+    }
+
+    #[test]
+    fn test_rank_of() {
+        let packets = ["[1,1,3,1,1]", "[[1],4]", "[9]", "[]", "[[2]]"].map(packet);
+        let keys = ["[[2]]", "[[6]]", "[0]"].map(packet);
+        assert_eq!(vec![5, 7, 2], rank_of(&packets, &keys));
+        assert_eq!(Vec::<usize>::new(), rank_of(&packets, &[]));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_conversion() {
+        let p = packet("[1,[2,[3,[4,[5,6,7]]]],8,9]");
+        let json = serde_json::Value::from(&p);
+        assert_eq!("[1,[2,[3,[4,[5,6,7]]]],8,9]", json.to_string());
+        assert_eq!(Ok(p), Packet::try_from(&json));
+
+        let bad: serde_json::Value = serde_json::from_str("[1,[256]]").unwrap();
+        assert!(Packet::try_from(&bad).is_err());
+        let bad: serde_json::Value = serde_json::from_str(r#"[1,"a"]"#).unwrap();
+        assert!(Packet::try_from(&bad).is_err());
+        let bad: serde_json::Value = serde_json::from_str("[-1]").unwrap();
+        assert!(Packet::try_from(&bad).is_err());
+    }
+
+    #[test]
+    fn test_streaming() {
+        let input = "[1,1,3,1,1]\n[1,1,5,1,1]\n\n[9]\n[[8,7,6]]\n\n[]\n[3]\n";
+        assert_eq!(4, sum_ordered_pairs(input.as_bytes()).unwrap());
+        let keys = ["[[2]]", "[[6]]"].map(packet);
+        assert_eq!(vec![4, 6], rank_of_stream(input.as_bytes(), &keys).unwrap());
+
+        assert!(sum_ordered_pairs("[1]\n[2]\n\n[3]\n".as_bytes()).is_err());
+        assert!(sum_ordered_pairs("[1]\n[1]\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for s in [
+            "",
+            "[",
+            "[1",
+            "[1,]",
+            "[,]",
+            "[1]]",
+            "[a]",
+            "256",
+            "[-1]",
+            "1 ",
+            "[\u{0663}]",
+        ] {
+            assert!(try_parse_packet(s).is_err(), "{s:?} should not parse");
+        }
+        let deep = "[".repeat(MAX_DEPTH + 2) + &"]".repeat(MAX_DEPTH + 2);
+        assert!(try_parse_packet(&deep).is_err());
+        assert_eq!(Ok(Packet::Value(7)), try_parse_packet("7"));
+    }
+
+    fn arb_packet() -> impl Strategy<Value = Packet> {
+        any::<u8>()
+            .prop_map(Packet::Value)
+            .prop_recursive(6, 64, 6, |inner| {
+                prop::collection::vec(inner, 0..6).prop_map(Packet::List)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_antisymmetric(a in arb_packet(), b in arb_packet()) {
+            prop_assert_eq!(compare_packets(&a, &b), compare_packets(&b, &a).map(|r| !r));
+        }
+
+        #[test]
+        fn prop_transitive(a in arb_packet(), b in arb_packet(), c in arb_packet()) {
+            if compare_packets(&a, &b) != Some(false) && compare_packets(&b, &c) != Some(false) {
+                prop_assert_ne!(compare_packets(&a, &c), Some(false));
+            }
+        }
+
+        #[test]
+        fn prop_round_trip(p in arb_packet()) {
+            prop_assert_eq!(Ok(p.clone()), try_parse_packet(&p.to_string()));
+        }
+
+        #[test]
+        fn prop_explain_agrees(a in arb_packet(), b in arb_packet()) {
+            prop_assert_eq!(compare_packets(&a, &b), explain_order(&a, &b).in_order);
+        }
+
+        #[test]
+        fn prop_parse_never_panics(bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+            let _ = try_parse_packet(&String::from_utf8_lossy(&bytes));
+        }
+
+        #[test]
+        fn prop_parse_packet_like_never_panics(s in "[\\[\\],0-9]{0,32}") {
+            let _ = try_parse_packet(&s);
+        }
+    }
+
+    #[test]
+    fn test_explain_integer_compare() {
+        let ex = explain_order(&packet("[1,1,3,1,1]"), &packet("[1,1,5,1,1]"));
+        assert_eq!(Some(true), ex.in_order);
+        assert_eq!(vec![2], ex.path);
+        assert_eq!(Some(OrderRule::IntegerCompare), ex.rule);
+        assert!(!ex.promoted);
+        assert_eq!(
+            "- Compare [1,1,3,1,1] vs [1,1,5,1,1]
+  - Compare 1 vs 1
+  - Compare 1 vs 1
+  - Compare 3 vs 5
+    - Left side is smaller, so inputs are in the right order
+",
+            ex.trace
+        );
+    }
+
+    #[test]
+    fn test_explain_promotion() {
+        let ex = explain_order(&packet("[[1],[2,3,4]]"), &packet("[[1],4]"));
+        assert_eq!(Some(true), ex.in_order);
+        assert_eq!(vec![1, 0], ex.path);
+        assert_eq!(Some(OrderRule::IntegerCompare), ex.rule);
+        assert!(ex.promoted);
+        assert_eq!(
+            "- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+",
+            ex.trace
+        );
+    }
+
+    #[test]
+    fn test_explain_list_exhaustion() {
+        let ex = explain_order(&packet("[7,7,7,7]"), &packet("[7,7,7]"));
+        assert_eq!(Some(false), ex.in_order);
+        assert_eq!(vec![3], ex.path);
+        assert_eq!(Some(OrderRule::ListExhaustion), ex.rule);
+
+        let ex = explain_order(&packet("[[[]]]"), &packet("[[]]"));
+        assert_eq!(Some(false), ex.in_order);
+        assert_eq!(vec![0, 0], ex.path);
+
+        let ex = explain_order(&packet("[1,2]"), &packet("[1,2]"));
+        assert_eq!(None, ex.in_order);
+        assert_eq!(None, ex.rule);
+        assert!(ex.path.is_empty());
+    }
+}
@@ -0,0 +1,1161 @@
+use crate::parse_utils;
+use std::{
+    io::{self, Write},
+    ops::Add,
+    sync::atomic::{AtomicI64, Ordering},
+    thread,
+};
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Self {
+        Point { x, y }
+    }
+
+    /// Maps to rotated coordinates `(x + y, x - y)`, where Manhattan diamonds
+    /// become axis-aligned squares.
+    pub fn rotated(&self) -> Point {
+        Point::new(self.x + self.y, self.x - self.y)
+    }
+
+    /// Inverse of `rotated`. Rotated points whose coordinates differ in
+    /// parity lie between cells and give `None`.
+    pub fn unrotated(&self) -> Option<Point> {
+        ((self.x + self.y) % 2 == 0)
+            .then(|| Point::new((self.x + self.y) / 2, (self.x - self.y) / 2))
+    }
+}
+
+impl Add for &Point {
+    type Output = Point;
+
+    fn add(self, other: Self) -> Self::Output {
+        Point {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+pub fn manhattan_distance(p1: &Point, p2: &Point) -> i64 {
+    (p1.x - p2.x).abs() + (p1.y - p2.y).abs()
+}
+
+/// How far a sensor reaches: it covers every cell no farther from it than
+/// its beacon is.
+pub trait Metric {
+    /// Distance between two cells. Only the order of distances matters, so
+    /// it need not be the true length.
+    fn distance(&self, a: &Point, b: &Point) -> i64;
+
+    /// Largest `|dx|` such that a cell `(dx, dy)` away lies within
+    /// `radius`, or `None` if no cell of that row does.
+    fn half_width(&self, radius: i64, dy: i64) -> Option<i64>;
+}
+
+/// Diamond-shaped coverage, as in the puzzle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    fn distance(&self, a: &Point, b: &Point) -> i64 {
+        manhattan_distance(a, b)
+    }
+
+    fn half_width(&self, radius: i64, dy: i64) -> Option<i64> {
+        let half = radius - dy.abs();
+        (half >= 0).then_some(half)
+    }
+}
+
+/// Square coverage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn distance(&self, a: &Point, b: &Point) -> i64 {
+        (a.x - b.x).abs().max((a.y - b.y).abs())
+    }
+
+    fn half_width(&self, radius: i64, dy: i64) -> Option<i64> {
+        (dy.abs() <= radius).then_some(radius)
+    }
+}
+
+/// Circular coverage. Distances are squared to stay in integers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Euclidean;
+
+impl Metric for Euclidean {
+    fn distance(&self, a: &Point, b: &Point) -> i64 {
+        (a.x - b.x).pow(2) + (a.y - b.y).pow(2)
+    }
+
+    fn half_width(&self, radius: i64, dy: i64) -> Option<i64> {
+        let rest = radius - dy * dy;
+        (rest >= 0).then(|| rest.isqrt())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sensor {
+    pub pos: Point,
+    pub beacon: Point,
+}
+
+impl Sensor {
+    /// Distance to the closest beacon; no other beacon lies within it.
+    pub fn radius(&self) -> i64 {
+        manhattan_distance(&self.pos, &self.beacon)
+    }
+
+    /// The covered diamond as a square in rotated coordinates.
+    pub fn rotated_square(&self) -> Rect {
+        let c = self.pos.rotated();
+        let r = self.radius();
+        Rect {
+            min: Point::new(c.x - r, c.y - r),
+            max: Point::new(c.x + r, c.y + r),
+        }
+    }
+}
+
+/// Sensors together with the metric their coverage is measured in. The
+/// coverage and gap-finding code lives here; the free functions of the same
+/// names use the puzzle's Manhattan metric. The boundary and rotation based
+/// solvers depend on diamond shapes and only exist for Manhattan.
+#[derive(Debug, Clone)]
+pub struct SensorField<'a, M> {
+    sensors: &'a [Sensor],
+    radii: Vec<i64>,
+    metric: M,
+}
+
+impl<'a, M: Metric> SensorField<'a, M> {
+    pub fn new(sensors: &'a [Sensor], metric: M) -> Self {
+        let radii = sensors
+            .iter()
+            .map(|s| metric.distance(&s.pos, &s.beacon))
+            .collect();
+        SensorField {
+            sensors,
+            radii,
+            metric,
+        }
+    }
+
+    pub fn sensors(&self) -> &'a [Sensor] {
+        self.sensors
+    }
+
+    /// Distance from sensor `i` to its beacon.
+    pub fn radius(&self, i: usize) -> i64 {
+        self.radii[i]
+    }
+
+    /// The part of row `y` covered by sensor `i`, as an inclusive x range.
+    pub fn row_interval(&self, i: usize, y: i64) -> Option<(i64, i64)> {
+        let pos = self.sensors[i].pos;
+        let half = self.metric.half_width(self.radii[i], y - pos.y)?;
+        Some((pos.x - half, pos.x + half))
+    }
+
+    pub fn is_covered(&self, p: &Point) -> bool {
+        (0..self.sensors.len())
+            .any(|i| self.metric.distance(&self.sensors[i].pos, p) <= self.radii[i])
+    }
+
+    /// The cells of row `y` covered by at least one sensor, as sorted,
+    /// disjoint inclusive intervals.
+    pub fn row_coverage(&self, y: i64) -> Vec<(i64, i64)> {
+        merge_intervals(
+            (0..self.sensors.len())
+                .filter_map(|i| self.row_interval(i, y))
+                .collect(),
+        )
+    }
+
+    /// Number of cells in row `y` where a beacon cannot be: covered cells
+    /// minus the known beacons on that row.
+    pub fn count_excluded(&self, y: i64) -> usize {
+        let coverage = self.row_coverage(y);
+        let mut beacons = self
+            .sensors
+            .iter()
+            .map(|s| s.beacon)
+            .filter(|b| b.y == y)
+            .collect::<Vec<_>>();
+        beacons.sort_unstable();
+        beacons.dedup();
+        let covered: i64 = coverage.iter().map(|(lo, hi)| hi - lo + 1).sum();
+        covered as usize - beacons.len()
+    }
+
+    /// Leftmost uncovered x in `min_x..=max_x` on row `y`. Inside a sensor's
+    /// reach the walk jumps straight past the end of its row interval, so
+    /// each row takes only a few steps.
+    fn scan_row(&self, min_x: i64, max_x: i64, y: i64) -> Option<i64> {
+        let mut x = min_x;
+        while x <= max_x {
+            match (0..self.sensors.len())
+                .filter_map(|i| self.row_interval(i, y))
+                .find(|&(lo, hi)| lo <= x && x <= hi)
+            {
+                Some((_, hi)) => x = hi + 1,
+                None => return Some(x),
+            }
+        }
+        None
+    }
+
+    /// The first uncovered cell of the box `min..=max`, by rows top to bottom.
+    pub fn find_uncovered_by_scan(&self, min: Point, max: Point) -> Option<Point> {
+        (min.y..=max.y).find_map(|y| self.scan_row(min.x, max.x, y).map(|x| Point::new(x, y)))
+    }
+
+    /// Same result as `find_uncovered_by_scan`, with rows dealt out round-robin
+    /// to `threads` threads.
+    ///
+    /// Once a thread finds a gap it publishes the row, and every thread stops
+    /// before going past it. Rows above a published one are still finished, so
+    /// the topmost gap wins however the threads are scheduled.
+    pub fn find_uncovered_by_scan_parallel(
+        &self,
+        min: Point,
+        max: Point,
+        threads: usize,
+    ) -> Option<Point>
+    where
+        M: Sync,
+    {
+        let threads = threads.max(1) as i64;
+        let found_row = AtomicI64::new(i64::MAX);
+        thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|t| {
+                    let found_row = &found_row;
+                    scope.spawn(move || {
+                        let mut y = min.y + t;
+                        while y <= max.y && y < found_row.load(Ordering::Relaxed) {
+                            if let Some(x) = self.scan_row(min.x, max.x, y) {
+                                found_row.fetch_min(y, Ordering::Relaxed);
+                                return Some(Point::new(x, y));
+                            }
+                            y += threads;
+                        }
+                        None
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .filter_map(|w| w.join().expect("Scan thread panicked"))
+                .min_by_key(|p| p.y)
+        })
+    }
+
+    /// The parts of the box `min..=max` that no sensor covers, row by row.
+    ///
+    /// Each row's gaps are the complement of its merged coverage. A gap spanning
+    /// the same columns as one in the row above extends that rectangle downwards,
+    /// so a region comes out as a stack of rectangles, one per change in width.
+    /// Rectangles are ordered by their top row, then left column.
+    pub fn uncovered_regions(&self, min: Point, max: Point) -> Vec<Rect> {
+        let mut done = Vec::new();
+        let mut open: Vec<Rect> = Vec::new();
+        for y in min.y..=max.y {
+            let mut gaps = Vec::new();
+            let mut x = min.x;
+            for (lo, hi) in self.row_coverage(y) {
+                if hi < x {
+                    continue;
+                }
+                if lo > max.x {
+                    break;
+                }
+                if lo > x {
+                    gaps.push((x, lo - 1));
+                }
+                x = hi + 1;
+            }
+            if x <= max.x {
+                gaps.push((x, max.x));
+            }
+
+            let mut next = Vec::with_capacity(gaps.len());
+            for (lo, hi) in gaps {
+                match open.iter().position(|r| r.min.x == lo && r.max.x == hi) {
+                    Some(i) => {
+                        let mut r = open.swap_remove(i);
+                        r.max.y = y;
+                        next.push(r);
+                    }
+                    None => next.push(Rect {
+                        min: Point::new(lo, y),
+                        max: Point::new(hi, y),
+                    }),
+                }
+            }
+            done.append(&mut open);
+            open = next;
+        }
+        done.append(&mut open);
+        done.sort_unstable_by_key(|r| (r.min.y, r.min.x));
+        done
+    }
+
+    pub fn coverage_at(&self, p: &Point) -> Coverage {
+        if self.sensors.iter().any(|s| s.pos == *p) {
+            Coverage::Sensor
+        } else if self.sensors.iter().any(|s| s.beacon == *p) {
+            Coverage::Beacon
+        } else if self.is_covered(p) {
+            Coverage::Covered
+        } else {
+            Coverage::Uncovered
+        }
+    }
+
+    /// Draws the window `min..=max` like the puzzle statement does.
+    pub fn render(&self, min: Point, max: Point) -> String {
+        let mut s = String::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                s.push(self.coverage_at(&Point::new(x, y)).symbol());
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Writes the window `min..=max` as a binary PPM image at most `size` pixels
+    /// on a side, so that huge areas fit on screen.
+    ///
+    /// Each pixel stands for a square block of cells. A block shows as uncovered
+    /// if any of its cells is, so even a single gap stays visible, and sensors and
+    /// beacons are drawn over that. `mark`, if given, is highlighted with a red
+    /// square a few pixels wide. An empty window or a `size` of zero is an
+    /// `InvalidInput` error.
+    pub fn write_ppm<W: Write>(
+        &self,
+        w: &mut W,
+        min: Point,
+        max: Point,
+        size: usize,
+        mark: Option<Point>,
+    ) -> io::Result<()> {
+        if size == 0 || min.x > max.x || min.y > max.y {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the image needs a positive size and a non-empty window",
+            ));
+        }
+        let cells = (max.x - min.x + 1).max(max.y - min.y + 1);
+        let block = (cells + size as i64 - 1) / size as i64;
+        let width = ((max.x - min.x) / block + 1) as usize;
+        let height = ((max.y - min.y) / block + 1) as usize;
+        let pixel = |p: &Point| {
+            let inside = (min.x..=max.x).contains(&p.x) && (min.y..=max.y).contains(&p.y);
+            inside.then(|| {
+                ((p.y - min.y) / block) as usize * width + ((p.x - min.x) / block) as usize
+            })
+        };
+
+        let mut image = vec![Coverage::Covered.color(); width * height];
+        for r in self.uncovered_regions(min, max) {
+            for y in (r.min.y - min.y) / block..=(r.max.y - min.y) / block {
+                for x in (r.min.x - min.x) / block..=(r.max.x - min.x) / block {
+                    image[y as usize * width + x as usize] = Coverage::Uncovered.color();
+                }
+            }
+        }
+        for s in self.sensors {
+            if let Some(i) = pixel(&s.beacon) {
+                image[i] = Coverage::Beacon.color();
+            }
+            if let Some(i) = pixel(&s.pos) {
+                image[i] = Coverage::Sensor.color();
+            }
+        }
+        if let Some(m) = mark.as_ref().and_then(pixel) {
+            let (mx, my) = ((m % width) as i64, (m / width) as i64);
+            for y in (my - 2).max(0)..=(my + 2).min(height as i64 - 1) {
+                for x in (mx - 2).max(0)..=(mx + 2).min(width as i64 - 1) {
+                    image[y as usize * width + x as usize] = [220, 30, 30];
+                }
+            }
+        }
+
+        write!(w, "P6\n{width} {height}\n255\n")?;
+        for color in image {
+            w.write_all(&color)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where to look for the distress beacon and how to report it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeaconSearch {
+    /// Inclusive range of x coordinates the beacon may have.
+    pub x_bounds: (i64, i64),
+    /// Inclusive range of y coordinates the beacon may have.
+    pub y_bounds: (i64, i64),
+    /// Tuning frequency is `x * tuning_multiplier + y`.
+    pub tuning_multiplier: i64,
+    /// Row checked for excluded positions in part 1.
+    pub target_row: i64,
+}
+
+impl BeaconSearch {
+    /// Settings used by the example in the puzzle text.
+    pub fn example() -> Self {
+        BeaconSearch {
+            x_bounds: (0, 20),
+            y_bounds: (0, 20),
+            target_row: 10,
+            ..Default::default()
+        }
+    }
+
+    pub fn min(&self) -> Point {
+        Point::new(self.x_bounds.0, self.y_bounds.0)
+    }
+
+    pub fn max(&self) -> Point {
+        Point::new(self.x_bounds.1, self.y_bounds.1)
+    }
+
+    pub fn tuning_frequency(&self, p: &Point) -> i64 {
+        p.x * self.tuning_multiplier + p.y
+    }
+}
+
+impl Default for BeaconSearch {
+    fn default() -> Self {
+        BeaconSearch {
+            x_bounds: (0, 4000000),
+            y_bounds: (0, 4000000),
+            tuning_multiplier: 4000000,
+            target_row: 2000000,
+        }
+    }
+}
+
+/// Parses lines like `Sensor at x=2, y=18: closest beacon is at x=-2, y=15`.
+/// Lines without exactly four numbers are skipped.
+pub fn parse_sensors(input: &str) -> Vec<Sensor> {
+    input
+        .lines()
+        .map(parse_utils::parse_signed_numbers)
+        .filter_map(|xs| match xs.as_slice() {
+            [x1, y1, x2, y2] => Some(Sensor {
+                pos: Point::new(*x1, *y1),
+                beacon: Point::new(*x2, *y2),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Sorts inclusive intervals and merges overlapping or adjacent ones.
+pub fn merge_intervals(mut intervals: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(intervals.len());
+    for (lo, hi) in intervals {
+        match merged.last_mut() {
+            Some(last) if lo <= last.1 + 1 => last.1 = last.1.max(hi),
+            _ => merged.push((lo, hi)),
+        }
+    }
+    merged
+}
+
+/// Merged coverage of row `y`. See `SensorField::row_coverage`.
+pub fn row_coverage(sensors: &[Sensor], y: i64) -> Vec<(i64, i64)> {
+    SensorField::new(sensors, Manhattan).row_coverage(y)
+}
+
+/// The part 1 answer for row `y`. See `SensorField::count_excluded`.
+pub fn count_excluded(sensors: &[Sensor], y: i64) -> usize {
+    SensorField::new(sensors, Manhattan).count_excluded(y)
+}
+
+/// Whether any sensor reaches `p`. Each call sets up a `SensorField`, so
+/// build one instead when checking many cells.
+pub fn is_covered(sensors: &[Sensor], p: &Point) -> bool {
+    SensorField::new(sensors, Manhattan).is_covered(p)
+}
+
+/// Scans the box `min..=max` row by row for a gap. See
+/// `SensorField::find_uncovered_by_scan`.
+pub fn find_uncovered_by_scan(sensors: &[Sensor], min: Point, max: Point) -> Option<Point> {
+    SensorField::new(sensors, Manhattan).find_uncovered_by_scan(min, max)
+}
+
+/// Multi-threaded `find_uncovered_by_scan`. See
+/// `SensorField::find_uncovered_by_scan_parallel`.
+pub fn find_uncovered_by_scan_parallel(
+    sensors: &[Sensor],
+    min: Point,
+    max: Point,
+    threads: usize,
+) -> Option<Point> {
+    SensorField::new(sensors, Manhattan).find_uncovered_by_scan_parallel(min, max, threads)
+}
+
+/// Finds an uncovered cell in the box `min..=max` without scanning it.
+///
+/// An uncovered cell next to a covered one is exactly one step outside that
+/// cell's sensor, so every gap region touches some sensor's perimeter at
+/// distance `radius + 1`, unless nothing in the box is covered at all. Each
+/// perimeter is walked inside the box, and a box corner handles the rest.
+pub fn find_uncovered_by_boundaries(sensors: &[Sensor], min: Point, max: Point) -> Option<Point> {
+    let field = SensorField::new(sensors, Manhattan);
+    let inside = |p: &Point| (min.x..=max.x).contains(&p.x) && (min.y..=max.y).contains(&p.y);
+    let perimeters = sensors.iter().flat_map(|s| {
+        let r = s.radius() + 1;
+        let (top, bottom) = ((s.pos.y - r).max(min.y), (s.pos.y + r).min(max.y));
+        (top..=bottom).flat_map(move |y| {
+            let half = r - (s.pos.y - y).abs();
+            [Point::new(s.pos.x - half, y), Point::new(s.pos.x + half, y)]
+        })
+    });
+    std::iter::once(min)
+        .chain(perimeters)
+        .find(|p| inside(p) && !field.is_covered(p))
+}
+
+/// An axis-aligned rectangle of cells, inclusive on both corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Rect {
+    pub fn area(&self) -> i64 {
+        (self.max.x - self.min.x + 1) * (self.max.y - self.min.y + 1)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Point> + '_ {
+        (self.min.y..=self.max.y)
+            .flat_map(move |y| (self.min.x..=self.max.x).map(move |x| Point::new(x, y)))
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let min = Point::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y));
+        let max = Point::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y));
+        (min.x <= max.x && min.y <= max.y).then_some(Rect { min, max })
+    }
+
+    /// The parts of `self` outside `other`: full-width strips above and below
+    /// the overlap, and the pieces left and right of it.
+    pub fn subtract(&self, other: &Rect) -> Vec<Rect> {
+        let Some(cut) = self.intersection(other) else {
+            return vec![*self];
+        };
+        let mut parts = Vec::with_capacity(4);
+        if self.min.y < cut.min.y {
+            parts.push(Rect {
+                min: self.min,
+                max: Point::new(self.max.x, cut.min.y - 1),
+            });
+        }
+        if cut.max.y < self.max.y {
+            parts.push(Rect {
+                min: Point::new(self.min.x, cut.max.y + 1),
+                max: self.max,
+            });
+        }
+        if self.min.x < cut.min.x {
+            parts.push(Rect {
+                min: Point::new(self.min.x, cut.min.y),
+                max: Point::new(cut.min.x - 1, cut.max.y),
+            });
+        }
+        if cut.max.x < self.max.x {
+            parts.push(Rect {
+                min: Point::new(cut.max.x + 1, cut.min.y),
+                max: Point::new(self.max.x, cut.max.y),
+            });
+        }
+        parts
+    }
+}
+
+/// The parts of the box `min..=max` that no sensor covers, as rectangles.
+/// See `SensorField::uncovered_regions`.
+pub fn uncovered_regions(sensors: &[Sensor], min: Point, max: Point) -> Vec<Rect> {
+    SensorField::new(sensors, Manhattan).uncovered_regions(min, max)
+}
+
+/// Number of integer points covered by at least one rectangle.
+pub fn union_area(rects: &[Rect]) -> i64 {
+    sweep_union(rects, |x, y| (x.1 - x.0 + 1) * (y.1 - y.0 + 1))
+}
+
+/// Number of cells covered by at least one sensor, anywhere.
+pub fn covered_cells(sensors: &[Sensor]) -> i64 {
+    let squares = sensors
+        .iter()
+        .map(Sensor::rotated_square)
+        .collect::<Vec<_>>();
+    sweep_union(&squares, |u, v| {
+        let (even_u, odd_u) = parity_counts(u);
+        let (even_v, odd_v) = parity_counts(v);
+        even_u * even_v + odd_u * odd_v
+    })
+}
+
+/// Splits the union into vertical slabs between rectangle edges, merges the
+/// y spans within each slab and adds up `count` for every resulting block.
+fn sweep_union(rects: &[Rect], count: impl Fn((i64, i64), (i64, i64)) -> i64) -> i64 {
+    let mut xs = rects
+        .iter()
+        .flat_map(|r| [r.min.x, r.max.x + 1])
+        .collect::<Vec<_>>();
+    xs.sort_unstable();
+    xs.dedup();
+    xs.windows(2)
+        .map(|w| {
+            let slab = (w[0], w[1] - 1);
+            let spans = rects
+                .iter()
+                .filter(|r| r.min.x <= slab.0 && slab.1 <= r.max.x)
+                .map(|r| (r.min.y, r.max.y))
+                .collect();
+            merge_intervals(spans)
+                .into_iter()
+                .map(|span| count(slab, span))
+                .sum::<i64>()
+        })
+        .sum()
+}
+
+/// Even and odd integers in the inclusive range.
+fn parity_counts((lo, hi): (i64, i64)) -> (i64, i64) {
+    let evens = hi.div_euclid(2) - (lo - 1).div_euclid(2);
+    (evens, hi - lo + 1 - evens)
+}
+
+/// The cells of the box `min..=max` inside `r`, a rectangle in rotated
+/// coordinates, as one `(u, first v, last v)` column per u. Consecutive cells
+/// of a column are two apart in v.
+fn rotated_columns(r: &Rect, min: Point, max: Point) -> impl Iterator<Item = (i64, i64, i64)> {
+    (r.min.x..=r.max.x).filter_map(move |u| {
+        let lo = r.min.y.max(2 * min.x - u).max(u - 2 * max.y);
+        let hi = r.max.y.min(2 * max.x - u).min(u - 2 * min.y);
+        let lo = lo + (lo - u).rem_euclid(2);
+        let hi = hi - (hi - u).rem_euclid(2);
+        (lo <= hi).then_some((u, lo, hi))
+    })
+}
+
+/// The parts of the box `min..=max` that no sensor covers, as rectangles in
+/// rotated coordinates.
+///
+/// The box turns into a diamond under rotation. Every sensor square is
+/// subtracted from the square around that diamond, and pieces that fall
+/// outside the diamond are dropped as soon as they appear.
+pub fn uncovered_rotated(sensors: &[Sensor], min: Point, max: Point) -> Vec<Rect> {
+    let (lo, hi) = (min.rotated(), max.rotated());
+    let bounds = Rect {
+        min: Point::new(lo.x, min.x - max.y),
+        max: Point::new(hi.x, max.x - min.y),
+    };
+    // Separating axes: the rectangle's own axes are covered by `bounds`, so
+    // only the diamond's edges (constant u + v and u - v) remain.
+    let touches_box = |r: &Rect| {
+        r.min.x + r.min.y <= 2 * max.x
+            && r.max.x + r.max.y >= 2 * min.x
+            && r.min.x - r.max.y <= 2 * max.y
+            && r.max.x - r.min.y >= 2 * min.y
+    };
+    let mut gaps = vec![bounds];
+    for s in sensors {
+        let square = s.rotated_square();
+        gaps = gaps
+            .iter()
+            .flat_map(|g| g.subtract(&square))
+            .filter(touches_box)
+            .collect();
+    }
+    gaps.retain(|g| rotated_columns(g, min, max).next().is_some());
+    gaps
+}
+
+/// Number of cells in the box `min..=max` covered by at least one sensor.
+pub fn covered_area(sensors: &[Sensor], min: Point, max: Point) -> i64 {
+    let uncovered = uncovered_rotated(sensors, min, max)
+        .iter()
+        .flat_map(|g| rotated_columns(g, min, max))
+        .map(|(_, lo, hi)| (hi - lo) / 2 + 1)
+        .sum::<i64>();
+    (max.x - min.x + 1) * (max.y - min.y + 1) - uncovered
+}
+
+/// Some cell of the box `min..=max` that no sensor covers, found by
+/// rectangle subtraction in rotated coordinates.
+pub fn find_uncovered_by_rotation(sensors: &[Sensor], min: Point, max: Point) -> Option<Point> {
+    uncovered_rotated(sensors, min, max)
+        .iter()
+        .find_map(|g| rotated_columns(g, min, max).next())
+        .and_then(|(u, v, _)| Point::new(u, v).unrotated())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coverage {
+    Sensor,
+    Beacon,
+    Covered,
+    Uncovered,
+}
+
+impl Coverage {
+    /// Character used for the cell in the puzzle statement.
+    pub fn symbol(self) -> char {
+        match self {
+            Coverage::Sensor => 'S',
+            Coverage::Beacon => 'B',
+            Coverage::Covered => '#',
+            Coverage::Uncovered => '.',
+        }
+    }
+
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Coverage::Sensor => [40, 200, 60],
+            Coverage::Beacon => [60, 120, 230],
+            Coverage::Covered => [70, 70, 80],
+            Coverage::Uncovered => [240, 240, 240],
+        }
+    }
+}
+
+/// What the puzzle would draw at `p`. Like `is_covered`, this sets up a
+/// `SensorField` on every call.
+pub fn coverage_at(sensors: &[Sensor], p: &Point) -> Coverage {
+    SensorField::new(sensors, Manhattan).coverage_at(p)
+}
+
+/// Text picture of the window `min..=max`. See `SensorField::render`.
+pub fn render_coverage(sensors: &[Sensor], min: Point, max: Point) -> String {
+    SensorField::new(sensors, Manhattan).render(min, max)
+}
+
+/// Writes the window `min..=max` as a downscaled PPM image. See
+/// `SensorField::write_ppm`.
+pub fn write_coverage_ppm<W: Write>(
+    w: &mut W,
+    sensors: &[Sensor],
+    min: Point,
+    max: Point,
+    size: usize,
+    mark: Option<Point>,
+) -> io::Result<()> {
+    SensorField::new(sensors, Manhattan).write_ppm(w, min, max, size, mark)
+}
+
+/// Random sensor layouts shared by the 2D and 3D property tests.
+#[cfg(test)]
+pub(crate) mod strategy {
+    use super::*;
+    use proptest::prelude::*;
+    use std::ops::Range;
+
+    /// A sensor built from its own and its beacon's coordinates.
+    pub(crate) trait FromCoords<const N: usize> {
+        fn from_coords(pos: [i64; N], beacon: [i64; N]) -> Self;
+    }
+
+    impl FromCoords<2> for Sensor {
+        fn from_coords(pos: [i64; 2], beacon: [i64; 2]) -> Self {
+            Sensor {
+                pos: Point::new(pos[0], pos[1]),
+                beacon: Point::new(beacon[0], beacon[1]),
+            }
+        }
+    }
+
+    /// `count` sensors inside `0..size` on every axis, each with its beacon at
+    /// most `reach` away along every axis.
+    pub(crate) fn sensors<S: FromCoords<N> + std::fmt::Debug, const N: usize>(
+        size: i64,
+        reach: i64,
+        count: Range<usize>,
+    ) -> impl Strategy<Value = Vec<S>> {
+        let sensor = (
+            prop::array::uniform::<_, N>(0..size),
+            prop::array::uniform::<_, N>(-reach..=reach),
+        )
+            .prop_map(|(pos, d)| S::from_coords(pos, std::array::from_fn(|i| pos[i] + d[i])));
+        prop::collection::vec(sensor, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_merge_intervals() {
+        assert_eq!(
+            vec![(-2, 5), (7, 9)],
+            merge_intervals(vec![(7, 8), (0, 5), (-2, 1), (8, 9)])
+        );
+        assert_eq!(vec![(0, 3)], merge_intervals(vec![(2, 3), (0, 1)]));
+        assert!(merge_intervals(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_row_interval() {
+        let sensors = [Sensor {
+            pos: Point::new(8, 7),
+            beacon: Point::new(2, 10),
+        }];
+        assert_eq!(9, sensors[0].radius());
+        let field = SensorField::new(&sensors, Manhattan);
+        assert_eq!(Some((-1, 17)), field.row_interval(0, 7));
+        assert_eq!(Some((8, 8)), field.row_interval(0, 16));
+        assert_eq!(None, field.row_interval(0, 17));
+    }
+
+    #[test]
+    fn test_count_excluded_large_coordinates() {
+        let sensors = parse_sensors(
+            "Sensor at x=0, y=0: closest beacon is at x=1000000000, y=0\n\
+             Sensor at x=1500000000, y=0: closest beacon is at x=1000000000, y=0",
+        );
+        assert_eq!(3000000000, count_excluded(&sensors, 0));
+    }
+
+    #[test]
+    fn test_find_uncovered_on_box_edge() {
+        // One sensor covering everything but the right column of the box.
+        let sensors = parse_sensors("Sensor at x=0, y=0: closest beacon is at x=0, y=4");
+        let p = find_uncovered_by_boundaries(&sensors, Point::new(-2, 0), Point::new(5, 0));
+        assert_eq!(Some(Point::new(5, 0)), p);
+        let p = find_uncovered_by_boundaries(&sensors, Point::new(-2, -2), Point::new(2, 2));
+        assert_eq!(None, p);
+    }
+
+    #[test]
+    fn test_uncovered_regions() {
+        // A sensor in the middle of a 7x7 box leaves its four corners open.
+        let sensors = parse_sensors("Sensor at x=3, y=3: closest beacon is at x=3, y=0");
+        let regions = uncovered_regions(&sensors, Point::new(0, 0), Point::new(6, 6));
+        let cells = regions.iter().map(Rect::area).sum::<i64>();
+        assert_eq!(49 - 25, cells);
+        assert_eq!(
+            Rect {
+                min: Point::new(0, 0),
+                max: Point::new(2, 0),
+            },
+            regions[0]
+        );
+        assert!(
+            regions
+                .iter()
+                .flat_map(Rect::cells)
+                .all(|p| !is_covered(&sensors, &p))
+        );
+
+        let regions = uncovered_regions(&sensors, Point::new(2, 2), Point::new(4, 4));
+        assert!(regions.is_empty());
+    }
+
+    #[test]
+    fn test_render_coverage() {
+        // The single sensor highlighted in the puzzle statement.
+        let sensors = parse_sensors("Sensor at x=8, y=7: closest beacon is at x=2, y=10");
+        assert_eq!(
+            "\
+..........#..........
+.........###.........
+........#####........
+.......#######.......
+......#########......
+.....###########.....
+....#############....
+...###############...
+..#################..
+.#########S#########.
+..#################..
+...###############...
+....B############....
+.....###########.....
+......#########......
+.......#######.......
+........#####........
+.........###.........
+..........#..........
+",
+            render_coverage(&sensors, Point::new(-2, -2), Point::new(18, 16))
+        );
+    }
+
+    #[test]
+    fn test_write_coverage_ppm() {
+        let sensors = parse_sensors("Sensor at x=3, y=3: closest beacon is at x=3, y=0");
+        let mut out = Vec::new();
+        write_coverage_ppm(
+            &mut out,
+            &sensors,
+            Point::new(0, 0),
+            Point::new(6, 6),
+            4,
+            None,
+        )
+        .unwrap();
+        // Two cells per pixel, so the sensor at (3, 3) and its beacon at
+        // (3, 0) both land in the second column.
+        let (header, pixels) = out.split_at(11);
+        assert_eq!(b"P6\n4 4\n255\n", header);
+        let colors = pixels.chunks(3).collect::<Vec<_>>();
+        assert_eq!(&Coverage::Beacon.color()[..], colors[1]);
+        assert_eq!(&Coverage::Sensor.color()[..], colors[5]);
+        assert_eq!(&Coverage::Uncovered.color()[..], colors[0]);
+
+        for (min, max, size) in [
+            (Point::new(0, 0), Point::new(6, 6), 0),
+            (Point::new(6, 0), Point::new(0, 6), 4),
+            (Point::new(0, 6), Point::new(6, 0), 4),
+        ] {
+            let err =
+                write_coverage_ppm(&mut Vec::new(), &sensors, min, max, size, None).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        }
+    }
+
+    #[test]
+    fn test_boundaries_gap_on_one_line() {
+        // Both sensors next to (3, 2) put it on the same x + y line, so it is
+        // not where a sum line crosses a difference line.
+        let sensors = parse_sensors(
+            "Sensor at x=7, y=0: closest beacon is at x=4, y=-2
+Sensor at x=6, y=7: closest beacon is at x=9, y=10
+Sensor at x=0, y=4: closest beacon is at x=3, y=3
+Sensor at x=0, y=1: closest beacon is at x=-1, y=0",
+        );
+        let (min, max) = (Point::new(0, 0), Point::new(7, 7));
+        let gap = Some(Point::new(3, 2));
+        assert_eq!(gap, find_uncovered_by_boundaries(&sensors, min, max));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let p = Point::new(3, -5);
+        assert_eq!(Point::new(-2, 8), p.rotated());
+        assert_eq!(Some(p), p.rotated().unrotated());
+        assert_eq!(None, Point::new(1, 0).unrotated());
+    }
+
+    #[test]
+    fn test_rect_subtract_and_union() {
+        let a = Rect {
+            min: Point::new(0, 0),
+            max: Point::new(4, 4),
+        };
+        let b = Rect {
+            min: Point::new(2, -1),
+            max: Point::new(3, 2),
+        };
+        let parts = a.subtract(&b);
+        assert_eq!(3, parts.len());
+        assert_eq!(25 - 6, parts.iter().map(Rect::area).sum::<i64>());
+        assert_eq!(25 + 2, union_area(&[a, b]));
+        assert_eq!(25, union_area(&[a, a]));
+        assert_eq!(
+            vec![a],
+            a.subtract(&Rect {
+                min: Point::new(5, 0),
+                max: Point::new(6, 1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_covered_cells() {
+        // Radius 3 diamond: 4 * 3 * (3 + 1) / 2 + 1 = 25 cells.
+        let sensors = parse_sensors("Sensor at x=3, y=3: closest beacon is at x=3, y=0");
+        assert_eq!(25, covered_cells(&sensors));
+        assert_eq!(
+            25,
+            covered_area(&sensors, Point::new(0, 0), Point::new(6, 6))
+        );
+        assert_eq!(
+            9,
+            covered_area(&sensors, Point::new(2, 2), Point::new(4, 4))
+        );
+        let gap = find_uncovered_by_rotation(&sensors, Point::new(0, 0), Point::new(6, 6));
+        assert!(!is_covered(&sensors, &gap.unwrap()));
+        assert_eq!(
+            None,
+            find_uncovered_by_rotation(&sensors, Point::new(2, 2), Point::new(4, 4))
+        );
+    }
+
+    #[test]
+    fn test_scan_parallel() {
+        // Gaps in several rows: every thread count must report the top one.
+        let sensors = parse_sensors(
+            "Sensor at x=10, y=10: closest beacon is at x=10, y=4\n\
+             Sensor at x=0, y=0: closest beacon is at x=5, y=0",
+        );
+        let (min, max) = (Point::new(0, 0), Point::new(12, 12));
+        let serial = find_uncovered_by_scan(&sensors, min, max);
+        assert_eq!(Some(Point::new(6, 0)), serial);
+        for threads in 0..6 {
+            assert_eq!(
+                serial,
+                find_uncovered_by_scan_parallel(&sensors, min, max, threads)
+            );
+        }
+        let max = Point::new(5, 0);
+        assert_eq!(None, find_uncovered_by_scan_parallel(&sensors, min, max, 3));
+    }
+
+    #[test]
+    fn test_chebyshev_field() {
+        let sensors = parse_sensors("Sensor at x=0, y=0: closest beacon is at x=2, y=1");
+        let field = SensorField::new(&sensors, Chebyshev);
+        assert_eq!(2, field.radius(0));
+        assert_eq!(vec![(-2, 2)], field.row_coverage(-2));
+        assert!(field.row_coverage(3).is_empty());
+        assert_eq!(4, field.count_excluded(1));
+        assert_eq!(
+            "\
+.......
+.#####.
+.#####.
+.##S##.
+.####B.
+.#####.
+.......
+",
+            field.render(Point::new(-3, -3), Point::new(3, 3))
+        );
+    }
+
+    #[test]
+    fn test_euclidean_field() {
+        let sensors = parse_sensors("Sensor at x=0, y=0: closest beacon is at x=3, y=4");
+        let field = SensorField::new(&sensors, Euclidean);
+        assert_eq!(25, field.radius(0));
+        assert_eq!(vec![(-3, 3)], field.row_coverage(4));
+        assert_eq!(vec![(0, 0)], field.row_coverage(-5));
+        // Lattice points in a circle of radius 5.
+        let (min, max) = (Point::new(-5, -5), Point::new(5, 5));
+        let uncovered = field
+            .uncovered_regions(min, max)
+            .iter()
+            .map(Rect::area)
+            .sum::<i64>();
+        assert_eq!(81, 121 - uncovered);
+        assert_eq!(
+            Some(Point::new(-5, -5)),
+            field.find_uncovered_by_scan(min, max)
+        );
+        // (-4, -3) lies exactly on the circle.
+        assert_eq!(
+            Some(Point::new(5, -3)),
+            field.find_uncovered_by_scan(Point::new(-4, -3), max)
+        );
+    }
+
+    fn check_field<M: Metric + Sync>(field: &SensorField<M>, min: Point, max: Point) {
+        let gaps = (min.y..=max.y)
+            .flat_map(|y| (min.x..=max.x).map(move |x| Point::new(x, y)))
+            .filter(|p| !field.is_covered(p))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gaps.first().copied(),
+            field.find_uncovered_by_scan(min, max)
+        );
+        assert_eq!(
+            gaps.first().copied(),
+            field.find_uncovered_by_scan_parallel(min, max, 3)
+        );
+        let mut cells = field
+            .uncovered_regions(min, max)
+            .iter()
+            .flat_map(Rect::cells)
+            .collect::<Vec<_>>();
+        cells.sort_unstable_by_key(|p| (p.y, p.x));
+        assert_eq!(gaps, cells);
+        for y in min.y..=max.y {
+            let covered = (min.x - 20..=max.x + 20)
+                .filter(|&x| field.is_covered(&Point::new(x, y)))
+                .count() as i64;
+            let merged = field
+                .row_coverage(y)
+                .iter()
+                .map(|(lo, hi)| hi - lo + 1)
+                .sum::<i64>();
+            assert_eq!(covered, merged);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_metrics_agree_with_brute_force(
+            sensors in strategy::sensors::<Sensor, 2>(12, 4, 1..6),
+        ) {
+            let (min, max) = (Point::new(0, 0), Point::new(11, 11));
+            check_field(&SensorField::new(&sensors, Manhattan), min, max);
+            check_field(&SensorField::new(&sensors, Chebyshev), min, max);
+            check_field(&SensorField::new(&sensors, Euclidean), min, max);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_boundaries_find_lone_gap(
+            sensors in strategy::sensors::<Sensor, 2>(16, 6, 1..10),
+        ) {
+            let (min, max) = (Point::new(0, 0), Point::new(15, 15));
+            let gaps = (0..=15)
+                .flat_map(|y| (0..=15).map(move |x| Point::new(x, y)))
+                .filter(|p| !is_covered(&sensors, p))
+                .collect::<Vec<_>>();
+            let found = find_uncovered_by_boundaries(&sensors, min, max);
+            match gaps.as_slice() {
+                [] => prop_assert_eq!(None, found),
+                [gap] => prop_assert_eq!(Some(*gap), found),
+                _ => prop_assert!(found.is_some_and(|p| gaps.contains(&p))),
+            }
+            let mut cells = uncovered_regions(&sensors, min, max)
+                .iter()
+                .flat_map(Rect::cells)
+                .collect::<Vec<_>>();
+            cells.sort_unstable_by_key(|p| (p.y, p.x));
+            prop_assert_eq!(&gaps, &cells);
+
+            prop_assert_eq!(256 - gaps.len() as i64, covered_area(&sensors, min, max));
+            prop_assert_eq!(gaps.first().copied(), find_uncovered_by_scan(&sensors, min, max));
+            prop_assert_eq!(
+                gaps.first().copied(),
+                find_uncovered_by_scan_parallel(&sensors, min, max, 3)
+            );
+            let found = find_uncovered_by_rotation(&sensors, min, max);
+            prop_assert_eq!(gaps.is_empty(), found.is_none());
+            prop_assert!(found.is_none_or(|p| gaps.contains(&p)));
+            let everywhere = (-30..50)
+                .map(|y| {
+                    row_coverage(&sensors, y)
+                        .iter()
+                        .map(|(lo, hi)| hi - lo + 1)
+                        .sum::<i64>()
+                })
+                .sum::<i64>();
+            prop_assert_eq!(everywhere, covered_cells(&sensors));
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{parse_utils, sensor::merge_intervals};
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct Point3 {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl Point3 {
+    pub fn new(x: i64, y: i64, z: i64) -> Self {
+        Point3 { x, y, z }
+    }
+}
+
+pub fn manhattan_distance(p1: &Point3, p2: &Point3) -> i64 {
+    (p1.x - p2.x).abs() + (p1.y - p2.y).abs() + (p1.z - p2.z).abs()
+}
+
+/// A sensor covering the octahedron of cells no farther from it than its
+/// beacon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sensor3 {
+    pub pos: Point3,
+    pub beacon: Point3,
+}
+
+impl Sensor3 {
+    pub fn radius(&self) -> i64 {
+        manhattan_distance(&self.pos, &self.beacon)
+    }
+
+    /// The part of the line at `y`, `z` covered by this sensor, as an
+    /// inclusive x range.
+    pub fn row_interval(&self, y: i64, z: i64) -> Option<(i64, i64)> {
+        let half = self.radius() - (self.pos.y - y).abs() - (self.pos.z - z).abs();
+        (half >= 0).then(|| (self.pos.x - half, self.pos.x + half))
+    }
+}
+
+/// Parses lines like
+/// `Sensor at x=2, y=18, z=3: closest beacon is at x=-2, y=15, z=4`.
+/// Lines without exactly six numbers are skipped.
+pub fn parse_sensors(input: &str) -> Vec<Sensor3> {
+    input
+        .lines()
+        .map(parse_utils::parse_signed_numbers)
+        .filter_map(|xs| match xs.as_slice() {
+            [x1, y1, z1, x2, y2, z2] => Some(Sensor3 {
+                pos: Point3::new(*x1, *y1, *z1),
+                beacon: Point3::new(*x2, *y2, *z2),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn is_covered(sensors: &[Sensor3], p: &Point3) -> bool {
+    sensors
+        .iter()
+        .any(|s| manhattan_distance(&s.pos, p) <= s.radius())
+}
+
+/// The cells of the line at `y`, `z` covered by at least one sensor, as
+/// sorted, disjoint inclusive x intervals.
+pub fn row_coverage(sensors: &[Sensor3], y: i64, z: i64) -> Vec<(i64, i64)> {
+    merge_intervals(
+        sensors
+            .iter()
+            .filter_map(|s| s.row_interval(y, z))
+            .collect(),
+    )
+}
+
+/// Number of cells in the box `min..=max` covered by at least one sensor.
+pub fn covered_volume(sensors: &[Sensor3], min: Point3, max: Point3) -> i64 {
+    let mut volume = 0;
+    for z in min.z..=max.z {
+        for y in min.y..=max.y {
+            volume += row_coverage(sensors, y, z)
+                .iter()
+                .map(|&(lo, hi)| (hi.min(max.x) - lo.max(min.x) + 1).max(0))
+                .sum::<i64>();
+        }
+    }
+    volume
+}
+
+/// The first uncovered cell of the box `min..=max`, ordered by z, then y,
+/// then x. Each line of constant y and z is walked like the 2D scan, jumping
+/// past the covered interval of whichever sensor reaches the current cell.
+pub fn find_uncovered_by_scan(sensors: &[Sensor3], min: Point3, max: Point3) -> Option<Point3> {
+    for z in min.z..=max.z {
+        for y in min.y..=max.y {
+            let mut x = min.x;
+            while x <= max.x {
+                match sensors
+                    .iter()
+                    .filter_map(|s| s.row_interval(y, z))
+                    .find(|&(lo, hi)| lo <= x && x <= hi)
+                {
+                    Some((_, hi)) => x = hi + 1,
+                    None => return Some(Point3::new(x, y, z)),
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensor::strategy::{self, FromCoords};
+    use proptest::prelude::*;
+
+    impl FromCoords<3> for Sensor3 {
+        fn from_coords(pos: [i64; 3], beacon: [i64; 3]) -> Self {
+            Sensor3 {
+                pos: Point3::new(pos[0], pos[1], pos[2]),
+                beacon: Point3::new(beacon[0], beacon[1], beacon[2]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_sensors() {
+        let sensors = parse_sensors(
+            "Sensor at x=2, y=18, z=-3: closest beacon is at x=-2, y=15, z=1\n\
+             Sensor at x=2, y=18: closest beacon is at x=-2, y=15",
+        );
+        assert_eq!(
+            vec![Sensor3 {
+                pos: Point3::new(2, 18, -3),
+                beacon: Point3::new(-2, 15, 1),
+            }],
+            sensors
+        );
+        assert_eq!(11, sensors[0].radius());
+        assert_eq!(Some((-7, 11)), sensors[0].row_interval(17, -2));
+    }
+
+    #[test]
+    fn test_octahedron_volume() {
+        // Radius 2: 1 + 6 + 18 cells at distance 0, 1 and 2.
+        let sensors = parse_sensors("Sensor at x=0, y=0, z=0: closest beacon is at x=0, y=0, z=2");
+        let (min, max) = (Point3::new(-3, -3, -3), Point3::new(3, 3, 3));
+        assert_eq!(25, covered_volume(&sensors, min, max));
+        assert_eq!(Some(min), find_uncovered_by_scan(&sensors, min, max));
+    }
+
+    #[test]
+    fn test_lone_gap() {
+        // Sensors on the corners of the 3x3x3 box, each reaching every cell
+        // on its side of the centre, but not the centre itself.
+        let sensors = parse_sensors(
+            "Sensor at x=1, y=1, z=1: closest beacon is at x=1, y=1, z=3
+Sensor at x=1, y=1, z=-1: closest beacon is at x=1, y=1, z=-3
+Sensor at x=1, y=-1, z=1: closest beacon is at x=1, y=-1, z=3
+Sensor at x=1, y=-1, z=-1: closest beacon is at x=1, y=-1, z=-3
+Sensor at x=-1, y=1, z=1: closest beacon is at x=-1, y=1, z=3
+Sensor at x=-1, y=1, z=-1: closest beacon is at x=-1, y=1, z=-3
+Sensor at x=-1, y=-1, z=1: closest beacon is at x=-1, y=-1, z=3
+Sensor at x=-1, y=-1, z=-1: closest beacon is at x=-1, y=-1, z=-3",
+        );
+        let (min, max) = (Point3::new(-1, -1, -1), Point3::new(1, 1, 1));
+        let origin = Some(Point3::new(0, 0, 0));
+        assert_eq!(origin, find_uncovered_by_scan(&sensors, min, max));
+        assert_eq!(26, covered_volume(&sensors, min, max));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_scan_agrees_with_brute_force(
+            sensors in strategy::sensors::<Sensor3, 3>(8, 4, 1..8),
+        ) {
+            let (min, max) = (Point3::new(0, 0, 0), Point3::new(7, 7, 7));
+            let gaps = (0..=7)
+                .flat_map(|z| (0..=7).flat_map(move |y| (0..=7).map(move |x| Point3::new(x, y, z))))
+                .filter(|p| !is_covered(&sensors, p))
+                .collect::<Vec<_>>();
+            prop_assert_eq!(512 - gaps.len() as i64, covered_volume(&sensors, min, max));
+            prop_assert_eq!(gaps.first().copied(), find_uncovered_by_scan(&sensors, min, max));
+        }
+    }
+}